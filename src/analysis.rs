@@ -0,0 +1,216 @@
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::record::*;
+use crate::calibration::Calibrated;
+
+// The values of all the moves of a position for each dice roll, at a given
+// depth: the moves are evaluated as by an `ExpectimaxPlayer` of this depth.
+pub struct Analysis {
+    pub rolls: Vec<RollAnalysis>,
+    // The expectimax value of the position.
+    pub value: f32,
+}
+
+pub struct RollAnalysis {
+    pub dice: usize,
+    // The possible moves with their values, in the order of
+    // `Board::possible_moves`.
+    pub moves: Vec<(usize, f32)>,
+}
+
+impl RollAnalysis {
+    // The best move and its value, or None if no move is possible.
+    pub fn best(&self) -> Option<(usize, f32)> {
+        let mut best: Option<(usize, f32)> = None;
+        for &(place, value) in &self.moves {
+            if best.is_none_or(|(_, best_value)| value > best_value) {
+                best = Some((place, value));
+            }
+        }
+
+        best
+    }
+}
+
+// Analyses a position. The depth must be at least 1.
+pub fn analyse<H: Heuristic>(h: &H, board: &Board, depth: u32) -> Analysis {
+    let rolls = (0..=4).map(|dice| {
+        let moves = board.possible_moves(dice).into_iter()
+            .map(|place| (place, eval_move(h, board, dice, place, depth - 1)))
+            .collect();
+        RollAnalysis { dice, moves }
+    }).collect();
+
+    Analysis { rolls, value: expectimax(h, board, depth) }
+}
+
+impl Analysis {
+    pub fn disp(&self) {
+        for roll in &self.rolls {
+            println!("Dice {} (probability {}/16)", roll.dice,
+                     PROBABILITIES[roll.dice] * 16.0);
+            let best = roll.best();
+            if best.is_none() {
+                println!("  no move");
+            }
+            for &(place, value) in &roll.moves {
+                let mark = if best == Some((place, value)) { " (best)" } else { "" };
+                println!("  {:<12} {:.4}{}",
+                         move_description(roll.dice, place), value, mark);
+            }
+        }
+        println!("Value: {:.4}", self.value);
+    }
+}
+
+// The equity losses from which a move is a mistake or a blunder. The equity of
+// a position is its value with a calibrated heuristic, 2p - 1 where p is the
+// win probability of the player that is to play (see `Calibrated`), so that it
+// is between -1 (lost) and 1 (won) whatever the heuristic.
+pub const MISTAKE: f32 = 0.04;
+pub const BLUNDER: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Judgement {
+    // The only possible move.
+    Forced,
+    // The best move, or a move losing less than `MISTAKE`.
+    Good,
+    Mistake,
+    Blunder,
+}
+
+// The annotation of a move of a game record.
+pub struct Annotation {
+    // The index of the roll in the record.
+    pub roll: usize,
+    // The player that moved.
+    pub player: usize,
+    pub dice: usize,
+    pub place: usize,
+    // The best move and the equity lost by playing `place` instead.
+    pub best: usize,
+    pub loss: f32,
+    pub judgement: Judgement,
+}
+
+// Annotates the moves of a game with the equity they lost compared to the best
+// move, as evaluated at the given depth. The rolls where no move is possible
+// are skipped.
+pub fn annotate<H: Heuristic>(h: &Calibrated<H>, record: &Record, depth: u32)
+    -> Vec<Annotation> {
+    let positions = record.positions();
+    let mut annotations = Vec::new();
+    for (i, roll) in record.rolls.iter().enumerate() {
+        let place = match roll.place {
+            Some(place) => place,
+            None => continue,
+        };
+        let board = &positions[i];
+        let moves = board.possible_moves(roll.dice);
+        let value = |place| eval_move(h, board, roll.dice, place, depth - 1);
+        let (best, loss) = if moves.len() == 1 {
+            (place, 0.0)
+        }
+        else {
+            let values: Vec<f32> = moves.iter().map(|&place| value(place)).collect();
+            let mut best = 0;
+            for (j, &v) in values.iter().enumerate() {
+                if v > values[best] {
+                    best = j;
+                }
+            }
+            let played = moves.iter().position(|&p| p == place).unwrap();
+            (moves[best], values[best] - values[played])
+        };
+        let judgement =
+            if moves.len() == 1 { Judgement::Forced }
+            else if loss >= BLUNDER { Judgement::Blunder }
+            else if loss >= MISTAKE { Judgement::Mistake }
+            else { Judgement::Good };
+        annotations.push(Annotation {
+            roll: i,
+            player: board.turn,
+            dice: roll.dice,
+            place,
+            best,
+            loss,
+            judgement,
+        });
+    }
+
+    annotations
+}
+
+// The summary of the annotations of a player.
+#[derive(Default)]
+pub struct Summary {
+    // The number of moves that were not forced.
+    pub decisions: u32,
+    // The number of those moves that were the best one.
+    pub best: u32,
+    pub mistakes: u32,
+    pub blunders: u32,
+    // The total equity lost.
+    pub loss: f32,
+}
+
+impl Summary {
+    // The rate of decisions where the best move was played.
+    pub fn accuracy(&self) -> f32 {
+        if self.decisions == 0 { 1.0 } else { self.best as f32 / self.decisions as f32 }
+    }
+}
+
+pub fn summarize(annotations: &[Annotation]) -> [Summary; 2] {
+    let mut summaries = [Summary::default(), Summary::default()];
+    for a in annotations {
+        if a.judgement == Judgement::Forced {
+            continue;
+        }
+        let summary = &mut summaries[a.player];
+        summary.decisions += 1;
+        if a.place == a.best {
+            summary.best += 1;
+        }
+        match a.judgement {
+            Judgement::Mistake => summary.mistakes += 1,
+            Judgement::Blunder => summary.blunders += 1,
+            _ => {}
+        }
+        summary.loss += a.loss;
+    }
+
+    summaries
+}
+
+// Prints the annotations of a game and the summary of each player.
+pub fn disp_annotations(annotations: &[Annotation]) {
+    for a in annotations {
+        let mut line = format!("{:>4}. {} {}: {:<12}", a.roll + 1, SYMBOLS[a.player],
+                               a.dice, move_description(a.dice, a.place));
+        match a.judgement {
+            Judgement::Forced => line += " (forced)",
+            Judgement::Good if a.place == a.best => {}
+            judgement => {
+                line += &format!(" loses {:.3} against {}", a.loss,
+                                 move_description(a.dice, a.best));
+                if judgement == Judgement::Mistake {
+                    line += " (mistake)";
+                }
+                else if judgement == Judgement::Blunder {
+                    line += " (blunder)";
+                }
+            }
+        }
+        println!("{}", line.trim_end());
+    }
+
+    println!();
+    for (player, summary) in summarize(annotations).iter().enumerate() {
+        println!("Player {}: {} decisions, accuracy {:.1}%, {} mistakes, \
+                  {} blunders, equity lost {:.3}",
+                 SYMBOLS[player], summary.decisions, 100.0 * summary.accuracy(),
+                 summary.mistakes, summary.blunders, summary.loss);
+    }
+}
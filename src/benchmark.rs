@@ -0,0 +1,110 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::tournament::*;
+use crate::players::*;
+use crate::supervised::sample_positions;
+
+// A fixed benchmark suite to follow the progress of a training run: matches
+// against fixed baselines, and the TD error on held-out positions.
+pub struct Benchmark {
+    // The held-out positions. They are the same for every run, so that the
+    // TD errors of different runs can be compared.
+    positions: Vec<Board>,
+    // The number of games of each match, as each color.
+    pub nb_games: u32,
+    // The depth of the expectimax players of the matches.
+    pub depth: u32,
+}
+
+// The results of a benchmark.
+pub struct BenchmarkResult {
+    // The rate of games won against `Greedy`.
+    pub vs_greedy: f32,
+    // The rate of games won against `SimpleHeuristic` at the same depth.
+    pub vs_advancement: f32,
+    // The mean squared difference between the evaluations of the held-out
+    // positions and their depth-1 expectimax values.
+    pub td_error: f32,
+}
+
+const HELD_OUT_SEED: u64 = 0x5eed;
+
+impl Benchmark {
+    // Creates a benchmark whose held-out positions are sampled from games
+    // between `Greedy` players.
+    pub fn new(nb_positions: usize, nb_games: u32, depth: u32) -> Benchmark {
+        let mut rng = StdRng::seed_from_u64(HELD_OUT_SEED);
+        let (positions, _) =
+            sample_positions(&mut Greedy {}, nb_positions, 10, &mut rng);
+
+        Benchmark { positions, nb_games, depth }
+    }
+
+    pub fn td_error<H: Heuristic>(&self, h: &H) -> f32 {
+        let sum: f32 = self.positions.iter().map(|board| {
+            let diff = expectimax(h, board, 1) - h.eval(board);
+            diff * diff
+        }).sum();
+
+        sum / self.positions.len() as f32
+    }
+
+    pub fn run<H: Heuristic + Clone>(&self, h: &H) -> BenchmarkResult {
+        let player = || ExpectimaxPlayer {
+            h: h.clone(),
+            depth: self.depth,
+            options: SearchOptions::default(),
+        };
+        let total = 2.0 * self.nb_games as f32;
+
+        let res = showdown(player(), Greedy {}, self.nb_games);
+        let vs_greedy = res[0] as f32 / total;
+        let advancement = ExpectimaxPlayer {
+            h: SimpleHeuristic {},
+            depth: self.depth,
+            options: SearchOptions::default(),
+        };
+        let res = showdown(player(), advancement, self.nb_games);
+        let vs_advancement = res[0] as f32 / total;
+
+        BenchmarkResult { vs_greedy, vs_advancement, td_error: self.td_error(h) }
+    }
+}
+
+// Appends the result of a benchmark after `games` training games to a CSV
+// learning curve. The header is written when the file is created.
+pub fn append_csv(path: &str, games: u32, result: &BenchmarkResult)
+    -> io::Result<()> {
+    let new = !Path::new(path).exists();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if new {
+        writeln!(file, "games,vs_greedy,vs_advancement,td_error")?;
+    }
+    writeln!(file, "{},{},{},{}", games, result.vs_greedy,
+             result.vs_advancement, result.td_error)
+}
+
+// Removes the rows of a CSV learning curve after `games` training games, so
+// that a resumed run does not duplicate them.
+pub fn truncate_csv(path: &str, games: u32) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut kept = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let row_games = line.split(',').next().and_then(|g| g.parse::<u32>().ok());
+        if i == 0 || row_games.is_some_and(|g| g <= games) {
+            kept += line;
+            kept += "\n";
+        }
+    }
+    fs::write(path, kept)
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::tournament::*;
+use crate::record::*;
+use crate::weights::write_atomically;
+
+// An opening book: the best move for each dice roll in the positions that can
+// be reached in the first rolls of a game, found by a deep expectimax search.
+//
+// Books are text files:
+//
+// ur-book <version>
+// rolls <number of rolls from the starting position>
+// depth <depth of the search>
+// <notation of a position>;<move with 1>;<move with 2>;<move with 3>;<move with 4>
+// ...
+//
+// where the moves are written as in the game records, see `move_notation`.
+pub struct Book {
+    pub rolls: u32,
+    pub depth: u32,
+    // The best move for each dice (None with 0, or when no move is possible).
+    pub moves: HashMap<Board, [Option<usize>; 5]>,
+}
+
+pub const VERSION: u32 = 1;
+
+// The largest number of rolls of a book. The number of positions grows
+// exponentially with the rolls, from 4043 after 6 rolls to 105234 after 10,
+// each of them searched at the depth of the book.
+pub const MAX_ROLLS: u32 = 10;
+
+// The positions that can be reached from `board` in at most `rolls` dice
+// rolls, whatever the moves. Finished games are not included.
+pub fn reachable(board: &Board, rolls: u32) -> HashSet<Board> {
+    let mut positions = HashSet::new();
+    positions.insert(board.clone());
+    let mut frontier = vec![board.clone()];
+    for _ in 0..rolls {
+        let mut next = Vec::new();
+        for board in &frontier {
+            for dice in 0..=4 {
+                let moves = board.possible_moves(dice);
+                let mut children = Vec::new();
+                if moves.is_empty() {
+                    let mut copy = board.clone();
+                    copy.change_turn();
+                    children.push(copy);
+                }
+                for place in moves {
+                    let mut copy = board.clone();
+                    if !copy.perform_move(dice, place) {
+                        children.push(copy);
+                    }
+                }
+                for child in children {
+                    if positions.insert(child.clone()) {
+                        next.push(child);
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    positions
+}
+
+impl Book {
+    // Builds the book of the positions reachable in the first `rolls` rolls,
+    // with the moves chosen as by an `ExpectimaxPlayer` of depth `depth`.
+    pub fn build<H: Heuristic>(h: &H, rolls: u32, depth: u32) -> Book {
+        let mut moves = HashMap::new();
+        for board in reachable(&Board::default(), rolls) {
+            let mut best = [None; 5];
+            for (dice, best) in best.iter_mut().enumerate() {
+                let possible = board.possible_moves(dice);
+                *best = match possible.len() {
+                    0 => None,
+                    1 => Some(possible[0]),
+                    _ => Some(best_move(h, &board, dice, depth - 1).0),
+                };
+            }
+            moves.insert(board, best);
+        }
+
+        Book { rolls, depth, moves }
+    }
+
+    // The move of the book, or None if the position is not in the book.
+    pub fn lookup(&self, board: &Board, dice: usize) -> Option<usize> {
+        self.moves.get(board).and_then(|moves| moves[dice])
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut lines: Vec<String> = self.moves.iter().map(|(board, moves)| {
+            let moves: Vec<String> = moves[1..].iter()
+                .map(|&place| move_notation(place))
+                .collect();
+            format!("{};{}", board.notation(), moves.join(";"))
+        }).collect();
+        // The order of the positions does not depend on the hash map.
+        lines.sort();
+
+        let mut text = format!("ur-book {}\n", VERSION);
+        text += &format!("rolls {}\n", self.rolls);
+        text += &format!("depth {}\n", self.depth);
+        for line in lines {
+            text += &line;
+            text += "\n";
+        }
+        write_atomically(path, &text)
+    }
+
+    pub fn load(path: &str) -> io::Result<Book> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |msg: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+        };
+        let mut lines = text.lines();
+        let mut field = |name: &str| {
+            lines.next()
+                .and_then(|l| l.strip_prefix(name))
+                .and_then(|l| l.strip_prefix(' '))
+                .map(|l| l.to_string())
+                .ok_or_else(|| invalid(format!("missing field {}", name)))
+        };
+
+        let version = field("ur-book")?;
+        if version != VERSION.to_string() {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let rolls = field("rolls")?.parse()
+            .map_err(|_| invalid("invalid number of rolls".to_string()))?;
+        let depth = field("depth")?.parse()
+            .map_err(|_| invalid("invalid depth".to_string()))?;
+
+        let mut moves = HashMap::new();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(';').collect();
+            let board = Board::from_notation(fields[0])
+                .map_err(|e| invalid(format!("{}: {}", e, line)))?;
+            if fields.len() != 5 {
+                return Err(invalid(format!("expected four moves: {}", line)));
+            }
+            let mut best = [None; 5];
+            for dice in 1..=4 {
+                best[dice] = parse_move(fields[dice])
+                    .ok_or_else(|| invalid(format!("invalid move: {}", line)))?;
+            }
+            moves.insert(board, best);
+        }
+
+        Ok(Book { rolls, depth, moves })
+    }
+}
+
+// Plays the moves of the book when the position is in it, and the moves of
+// `fallback` otherwise.
+pub struct BookPlayer<P: Player> {
+    pub book: Book,
+    pub fallback: P,
+    // The number of moves taken from the book.
+    pub hits: u32,
+}
+
+impl<P: Player> BookPlayer<P> {
+    pub fn new(book: Book, fallback: P) -> BookPlayer<P> {
+        BookPlayer { book, fallback, hits: 0 }
+    }
+}
+
+impl<P: Player> Player for BookPlayer<P> {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        match self.book.lookup(board, dice) {
+            // A book move is only played if it is legal, in case the book
+            // was built for other rules.
+            Some(place) if board.possible_moves(dice).contains(&place) => {
+                self.hits += 1;
+                place
+            }
+            _ => self.fallback.choose_move(board, dice),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::fs;
+use std::io;
+use rand::Rng;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::weights::write_atomically;
+
+// A logistic mapping from the evaluation `v` of a heuristic to a win
+// probability: p = 1 / (1 + exp(-(scale * v + bias))).
+#[derive(Clone, Copy)]
+pub struct Logistic {
+    pub scale: f32,
+    pub bias: f32,
+}
+
+impl Logistic {
+    pub fn apply(&self, val: f32) -> f32 {
+        1.0 / (1.0 + (-(self.scale * val + self.bias)).exp())
+    }
+
+    // Fits the mapping to the samples (v, won) by maximum likelihood, with
+    // Newton's method.
+    pub fn fit(samples: &[(f32, bool)]) -> Logistic {
+        let mut a = 0.0f64;
+        let mut b = 0.0f64;
+        for _ in 0..50 {
+            // Gradient and hessian of the log-likelihood.
+            let (mut ga, mut gb) = (0.0, 0.0);
+            let (mut haa, mut hab, mut hbb) = (0.0, 0.0, 0.0);
+            for &(v, won) in samples {
+                let v = v as f64;
+                let p = 1.0 / (1.0 + (-(a * v + b)).exp());
+                let err = if won { 1.0 - p } else { -p };
+                ga += err * v;
+                gb += err;
+                let w = p * (1.0 - p);
+                haa += w * v * v;
+                hab += w * v;
+                hbb += w;
+            }
+            // A small ridge keeps the system invertible when the samples are
+            // separable.
+            haa += 1e-6;
+            hbb += 1e-6;
+            let det = haa * hbb - hab * hab;
+            let da = (hbb * ga - hab * gb) / det;
+            let db = (haa * gb - hab * ga) / det;
+            a += da;
+            b += db;
+            if da.abs() < 1e-9 && db.abs() < 1e-9 {
+                break;
+            }
+        }
+
+        Logistic { scale: a as f32, bias: b as f32 }
+    }
+
+    // Writes the mapping to a text file:
+    //
+    // ur-calibration <version>
+    // scale <scale>
+    // bias <bias>
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text = format!("ur-calibration {}\nscale {}\nbias {}\n",
+                           VERSION, self.scale, self.bias);
+        write_atomically(path, &text)
+    }
+
+    pub fn load(path: &str) -> io::Result<Logistic> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |msg: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+        };
+        let mut lines = text.lines();
+        let mut field = |name: &str| {
+            lines.next()
+                .and_then(|l| l.strip_prefix(name))
+                .and_then(|l| l.strip_prefix(' '))
+                .map(|l| l.to_string())
+                .ok_or_else(|| invalid(format!("missing field {}", name)))
+        };
+
+        let version = field("ur-calibration")?;
+        if version != VERSION.to_string() {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let scale = field("scale")?.parse()
+            .map_err(|_| invalid("invalid scale".to_string()))?;
+        let bias = field("bias")?.parse()
+            .map_err(|_| invalid("invalid bias".to_string()))?;
+
+        Ok(Logistic { scale, bias })
+    }
+}
+
+pub const VERSION: u32 = 1;
+
+// Plays `nb_games` games where both players play the best move according to
+// `h` at depth 0, and records, for each position reached, the evaluation of
+// `h` and whether the player to play won the game.
+pub fn self_play_samples<H, R>(h: &H, nb_games: u32, rng: &mut R)
+    -> Vec<(f32, bool)>
+where H: Heuristic, R: Rng
+{
+    let mut samples = Vec::new();
+    for _ in 0..nb_games {
+        let mut board = Board::default();
+        let mut positions = Vec::new();
+        let winner = loop {
+            positions.push((h.eval(&board), board.turn));
+            let dice = roll_dices(rng);
+            if board.possible_moves(dice).is_empty() {
+                board.change_turn();
+                continue;
+            }
+            let (place, _) = best_move(h, &board, dice, 0);
+            if board.perform_move(dice, place) {
+                break board.turn;
+            }
+        };
+        samples.extend(positions.iter().map(|&(v, p)| (v, p == winner)));
+    }
+
+    samples
+}
+
+// Fits the logistic mapping of `h` over `nb_games` self-play games.
+pub fn calibrate<H, R>(h: &H, nb_games: u32, rng: &mut R) -> Logistic
+where H: Heuristic, R: Rng
+{
+    Logistic::fit(&self_play_samples(h, nb_games, rng))
+}
+
+// A heuristic together with its calibration. Its evaluation is 2p - 1, where
+// p is the calibrated win probability, so that it stays in [-1, 1] and can be
+// used in expectimax. The exact values of `h`, if any, are kept as they are
+// already win probabilities.
+pub struct Calibrated<H: Heuristic> {
+    pub h: H,
+    pub logistic: Logistic,
+}
+
+impl<H: Heuristic> Heuristic for Calibrated<H> {
+    fn victory() -> f32 {
+        1.0
+    }
+
+    fn eval(&self, board: &Board) -> f32 {
+        2.0 * self.win_probability(board) - 1.0
+    }
+
+    fn exact(&self, board: &Board) -> Option<f32> {
+        self.h.exact(board).map(|v| v / H::victory())
+    }
+}
+
+impl<H: Heuristic> WinProbability for Calibrated<H> {
+    fn win_probability(&self, board: &Board) -> f32 {
+        match self.exact(board) {
+            Some(v) => (v + 1.0) / 2.0,
+            None => self.logistic.apply(self.h.eval(board)),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::io;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::weights::write_atomically;
+
+// The state of a training run, written periodically so that long runs can be
+// resumed after an interruption. The evaluator itself is stored in a weight
+// file (see `weights`), whose path is recorded here.
+//
+// The random generator of each game is seeded from the seed of the run and
+// the index of the game (see `game_rng`), so the state of the randomness is
+// fully described by the seed and the number of games played.
+//
+// Checkpoint files are text files with one "key value" field per line.
+pub struct Checkpoint {
+    // The training mode, e.g. "td-lambda".
+    pub mode: String,
+    // The kind of evaluator, e.g. "linear".
+    pub model: String,
+    // The number of games played so far.
+    pub games: u32,
+    // The total number of games of the run.
+    pub nb_games: u32,
+    pub seed: u64,
+    // The initial learning coefficient of the schedule.
+    pub alpha: f32,
+    // The weight file of the evaluator.
+    pub weights: String,
+    // The CSV file of the learning curve.
+    pub curve: String,
+    // The number of games between two benchmarks, 0 if they are disabled.
+    pub benchmark: u32,
+    // The exploration policy, as given to `training --explore`.
+    pub exploration: String,
+    // The number of self-play threads.
+    pub workers: u32,
+    // The position database where the self-play games are added, if any.
+    pub database: Option<String>,
+}
+
+pub const VERSION: u32 = 5;
+
+// The random generator of the game number `game` of a run.
+pub fn game_rng(seed: u64, game: u32) -> StdRng {
+    StdRng::seed_from_u64(seed ^ (game as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+impl Checkpoint {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut text = format!("ur-checkpoint {}\n", VERSION);
+        text += &format!("mode {}\n", self.mode);
+        text += &format!("model {}\n", self.model);
+        text += &format!("games {}\n", self.games);
+        text += &format!("nb_games {}\n", self.nb_games);
+        text += &format!("seed {}\n", self.seed);
+        text += &format!("alpha {}\n", self.alpha);
+        text += &format!("weights {}\n", self.weights);
+        text += &format!("curve {}\n", self.curve);
+        text += &format!("benchmark {}\n", self.benchmark);
+        text += &format!("exploration {}\n", self.exploration);
+        text += &format!("workers {}\n", self.workers);
+        text += &format!("database {}\n", self.database.as_deref().unwrap_or(""));
+        write_atomically(path, &text)
+    }
+
+    pub fn load(path: &str) -> io::Result<Checkpoint> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |msg: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+        };
+        let mut lines = text.lines();
+        let mut field = |name: &str| {
+            lines.next()
+                .and_then(|l| l.strip_prefix(name))
+                .and_then(|l| l.strip_prefix(' '))
+                .map(|l| l.to_string())
+                .ok_or_else(|| invalid(format!("missing field {}", name)))
+        };
+
+        let version = field("ur-checkpoint")?;
+        if version != VERSION.to_string() {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let mode = field("mode")?;
+        let model = field("model")?;
+        let games = field("games")?.parse()
+            .map_err(|_| invalid("invalid number of games".to_string()))?;
+        let nb_games = field("nb_games")?.parse()
+            .map_err(|_| invalid("invalid total number of games".to_string()))?;
+        let seed = field("seed")?.parse()
+            .map_err(|_| invalid("invalid seed".to_string()))?;
+        let alpha = field("alpha")?.parse()
+            .map_err(|_| invalid("invalid learning coefficient".to_string()))?;
+        let weights = field("weights")?;
+        let curve = field("curve")?;
+        let benchmark = field("benchmark")?.parse()
+            .map_err(|_| invalid("invalid benchmark interval".to_string()))?;
+        let exploration = field("exploration")?;
+        let workers = field("workers")?.parse()
+            .map_err(|_| invalid("invalid number of workers".to_string()))?;
+        let database = Some(field("database")?).filter(|path| !path.is_empty());
+
+        Ok(Checkpoint {
+            mode, model, games, nb_games, seed, alpha, weights, curve, benchmark,
+            exploration, workers, database
+        })
+    }
+}
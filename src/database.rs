@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use crate::game_manager::*;
+use crate::record::*;
+use crate::reinforcement_learning::Game;
+use crate::weights::write_atomically;
+
+// A database of the positions seen in games, with statistics about them.
+//
+// Databases are text files:
+//
+// ur-positions <version>
+// <notation>;<times seen>;<wins>;<moves>
+// ...
+//
+// with one line per position, where the wins are the ones of the player that
+// is to play. The moves are the statistics of the moves played, separated by
+// commas, each written "<dice> <move> <times played> <wins>" with the moves
+// written as in the game records (see `move_notation`).
+#[derive(Default)]
+pub struct Database {
+    pub positions: HashMap<Board, PositionStats>,
+}
+
+#[derive(Default)]
+pub struct PositionStats {
+    pub seen: u32,
+    // The number of games won by the player that is to play.
+    pub wins: u32,
+    pub moves: Vec<MoveStats>,
+}
+
+#[derive(Clone, Copy)]
+pub struct MoveStats {
+    pub dice: usize,
+    pub place: usize,
+    pub played: u32,
+    // The number of games won by the player that played the move.
+    pub wins: u32,
+}
+
+pub const VERSION: u32 = 1;
+
+// The minimum number of times a move must have been played to be the best
+// known move.
+const MIN_PLAYED: u32 = 5;
+
+impl PositionStats {
+    pub fn win_rate(&self) -> f32 {
+        self.wins as f32 / self.seen as f32
+    }
+
+    // The move with the best win rate with a dice roll, among the moves played
+    // at least `MIN_PLAYED` times.
+    pub fn best_move(&self, dice: usize) -> Option<&MoveStats> {
+        let rate = |m: &MoveStats| m.wins as f32 / m.played as f32;
+        let mut best: Option<&MoveStats> = None;
+        let moves = self.moves.iter()
+            .filter(|m| m.dice == dice && m.played >= MIN_PLAYED);
+        for m in moves {
+            if best.is_none_or(|b| rate(m) > rate(b)) {
+                best = Some(m);
+            }
+        }
+
+        best
+    }
+
+    fn add_move(&mut self, dice: usize, place: usize, won: bool) {
+        let index = self.moves.iter().position(|m| m.dice == dice && m.place == place);
+        let stats = match index {
+            Some(i) => &mut self.moves[i],
+            None => {
+                self.moves.push(MoveStats { dice, place, played: 0, wins: 0 });
+                self.moves.last_mut().unwrap()
+            }
+        };
+        stats.played += 1;
+        stats.wins += won as u32;
+    }
+}
+
+impl Database {
+    // Adds the positions of a self-play game, whose moves are not known.
+    pub fn add_game(&mut self, game: &Game) {
+        for board in &game.positions {
+            let stats = self.positions.entry(board.clone()).or_default();
+            stats.seen += 1;
+            stats.wins += (board.turn == game.winner) as u32;
+        }
+    }
+
+    // Adds the positions and the moves of a finished game.
+    pub fn add_record(&mut self, record: &Record) {
+        let winner = match record.winner() {
+            Some(winner) => winner,
+            None => return,
+        };
+        for (board, roll) in record.positions().iter().zip(&record.rolls) {
+            let won = board.turn == winner;
+            let stats = self.positions.entry(board.clone()).or_default();
+            stats.seen += 1;
+            stats.wins += won as u32;
+            if let Some(place) = roll.place {
+                stats.add_move(roll.dice, place, won);
+            }
+        }
+    }
+
+    // Adds the statistics of another database.
+    pub fn merge(&mut self, other: &Database) {
+        for (board, other) in &other.positions {
+            let stats = self.positions.entry(board.clone()).or_default();
+            stats.seen += other.seen;
+            stats.wins += other.wins;
+            for &m in &other.moves {
+                let index = stats.moves.iter()
+                    .position(|s| s.dice == m.dice && s.place == m.place);
+                match index {
+                    Some(i) => {
+                        stats.moves[i].played += m.played;
+                        stats.moves[i].wins += m.wins;
+                    }
+                    None => stats.moves.push(m),
+                }
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut lines: Vec<String> = self.positions.iter().map(|(board, stats)| {
+            let moves: Vec<String> = stats.moves.iter().map(|m| {
+                format!("{} {} {} {}", m.dice, move_notation(Some(m.place)),
+                        m.played, m.wins)
+            }).collect();
+            format!("{};{};{};{}", board.notation(), stats.seen, stats.wins,
+                    moves.join(","))
+        }).collect();
+        lines.sort();
+
+        let mut text = format!("ur-positions {}\n", VERSION);
+        for line in lines {
+            text += &line;
+            text += "\n";
+        }
+        write_atomically(path, &text)
+    }
+
+    // Reads a database written by `save`. A missing file is an empty database.
+    pub fn load(path: &str) -> io::Result<Database> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Database::default());
+            }
+            Err(e) => return Err(e),
+        };
+        let invalid = |msg: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+        };
+        let mut lines = text.lines();
+        let version = lines.next().and_then(|l| l.strip_prefix("ur-positions "));
+        if version != Some(&VERSION.to_string()) {
+            return Err(invalid("unsupported version".to_string()));
+        }
+
+        let mut db = Database::default();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let notation = line.split(';').next().unwrap_or_default();
+            let board = Board::from_notation(notation)
+                .map_err(|e| invalid(format!("{}: {}", e, line)))?;
+            let stats = parse_stats(line)
+                .ok_or_else(|| invalid(format!("invalid statistics: {}", line)))?;
+            db.positions.insert(board, stats);
+        }
+
+        Ok(db)
+    }
+}
+
+// Reads the statistics of a line of a database, after the position.
+fn parse_stats(line: &str) -> Option<PositionStats> {
+    let fields: Vec<&str> = line.split(';').collect();
+    if fields.len() != 4 {
+        return None;
+    }
+    let mut stats = PositionStats {
+        seen: fields[1].parse().ok()?,
+        wins: fields[2].parse().ok()?,
+        moves: Vec::new(),
+    };
+    for m in fields[3].split(',').filter(|m| !m.is_empty()) {
+        let m: Vec<&str> = m.split_whitespace().collect();
+        if m.len() != 4 {
+            return None;
+        }
+        stats.moves.push(MoveStats {
+            dice: m[0].parse().ok().filter(|&dice| dice <= 4)?,
+            place: parse_move(m[1])??,
+            played: m[2].parse().ok()?,
+            wins: m[3].parse().ok()?,
+        });
+    }
+
+    Some(stats)
+}
@@ -0,0 +1,148 @@
+use crate::game_manager::*;
+
+// The number of elements of `Features::to_vec`.
+pub const NB_FEATURES: usize = 40;
+
+// Features of a board that are not directly visible from the occupancy of the
+// cells. They can be used as inputs of the evaluators, or to analyse games.
+//
+// Every array is indexed by the player, relatively to the player that is to
+// play: index 0 is the player that is to play, index 1 is the adversary.
+pub struct Features {
+    // danger[p][i] is the probability that the piece of player p at index i is
+    // taken at the next turn of the adversary of p, if nothing else moves.
+    pub danger: [[f32; 14]; 2],
+    // The sum of the probabilities of danger: the expected number of pieces
+    // taken at the next turn of the adversary.
+    pub expected_losses: [f32; 2],
+    // The number of pieces on a rosetta.
+    pub on_rosettas: [u8; 2],
+    // 1 if the player to play holds the central rosetta, -1 if the adversary
+    // holds it, 0 otherwise.
+    pub central_rosetta: f32,
+    // The expected number of legal moves over the dice results.
+    pub mobility: [f32; 2],
+    // The number of cells the pieces still have to travel to go out. A piece
+    // that has not entered the board needs 15 cells.
+    pub pips: [u32; 2],
+    // The number of pieces on the board that cannot move whatever the dice.
+    pub blocked: [u8; 2],
+}
+
+// The names of the elements of `Features::to_vec`, in the same order.
+pub fn names() -> Vec<String> {
+    let mut names = Vec::new();
+    for who in &["own", "adv"] {
+        for i in 0..14 {
+            names.push(format!("{}_danger_{}", who, i));
+        }
+    }
+    for name in &["expected_losses", "on_rosettas", "mobility", "pips", "blocked"] {
+        names.push(format!("own_{}", name));
+        names.push(format!("adv_{}", name));
+    }
+    names.push("central_rosetta".to_string());
+    names.push("pip_difference".to_string());
+
+    names
+}
+
+// The probability that the piece of `player` at index `idx` is taken by the
+// adversary at its next turn. Pieces can only be taken in the central row,
+// out of the central rosetta, by an adversary's piece that is 1 to 4 cells
+// behind.
+pub fn capture_probability(board: &Board, player: usize, idx: usize) -> f32 {
+    if !board.cells[player][idx] || !is_central(idx) || is_rosetta(idx) {
+        return 0.0;
+    }
+    let adv = 1 - player;
+    let mut proba = 0.0;
+    for (dice, &p) in PROBABILITIES.iter().enumerate().skip(1) {
+        if dice <= idx && board.cells[adv][idx - dice] {
+            proba += p;
+        }
+    }
+
+    proba
+}
+
+// Returns true if the piece at `idx` can move with a dice roll `dice`,
+// whoever is to play.
+fn can_move(board: &Board, player: usize, idx: usize, dice: usize) -> bool {
+    let mut copy = board.clone();
+    copy.turn = player;
+    copy.possible_moves(dice).contains(&idx)
+}
+
+impl Features {
+    pub fn new(board: &Board) -> Features {
+        let players = [board.turn, 1 - board.turn];
+        let mut features = Features {
+            danger: [[0.0; 14]; 2],
+            expected_losses: [0.0; 2],
+            on_rosettas: [0; 2],
+            central_rosetta: 0.0,
+            mobility: [0.0; 2],
+            pips: [0; 2],
+            blocked: [0; 2],
+        };
+
+        for (k, &p) in players.iter().enumerate() {
+            let mut copy = board.clone();
+            copy.turn = p;
+            for (dice, &proba) in PROBABILITIES.iter().enumerate() {
+                features.mobility[k] += proba * copy.possible_moves(dice).len() as f32;
+            }
+
+            features.pips[k] = 15 * board.ready[p] as u32;
+            for i in 0..14 {
+                if !board.cells[p][i] {
+                    continue;
+                }
+                features.danger[k][i] = capture_probability(board, p, i);
+                features.expected_losses[k] += features.danger[k][i];
+                if is_rosetta(i) {
+                    features.on_rosettas[k] += 1;
+                }
+                features.pips[k] += 14 - i as u32;
+                if !(1..=4).any(|dice| can_move(board, p, i, dice)) {
+                    features.blocked[k] += 1;
+                }
+            }
+        }
+
+        if board.cells[players[0]][CENTRAL_ROSETTA] {
+            features.central_rosetta = 1.0;
+        }
+        else if board.cells[players[1]][CENTRAL_ROSETTA] {
+            features.central_rosetta = -1.0;
+        }
+
+        features
+    }
+
+    // The features as a vector of numbers of similar magnitude, to be used as
+    // the inputs of an evaluator. See `names` for the meaning of each element.
+    pub fn to_vec(&self) -> Vec<f32> {
+        let mut v = Vec::with_capacity(NB_FEATURES);
+        v.extend(self.danger.concat());
+        v.extend(self.expected_losses);
+        v.extend(self.on_rosettas.iter().map(|&n| n as f32 / 7.0));
+        v.extend(self.mobility.iter().map(|&m| m / 7.0));
+        v.extend(self.pips.iter().map(|&n| n as f32 / 105.0));
+        v.extend(self.blocked.iter().map(|&n| n as f32 / 7.0));
+        v.push(self.central_rosetta);
+        v.push((self.pips[1] as f32 - self.pips[0] as f32) / 105.0);
+
+        v
+    }
+
+    // Display the features that are not zero.
+    pub fn disp(&self) {
+        for (name, x) in names().iter().zip(self.to_vec()) {
+            if x != 0.0 {
+                println!("{:20}: {}", name, x);
+            }
+        }
+    }
+}
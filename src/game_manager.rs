@@ -1,6 +1,10 @@
 use std::default::Default;
+use std::fmt;
 use rand::Rng;
 
+// The name of the rules implemented here, stored with the trained evaluators.
+pub const RULES: &str = "standard";
+
 // The probability of getting each dice results.
 pub const PROBABILITIES: [f32; 5] =
     [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
@@ -33,20 +37,20 @@ pub const fn is_central(idx: usize) -> bool {
 }
 
 pub const fn is_rosetta(idx: usize) -> bool {
-    match idx {
-        3 | 7 | 13 => true,
-        _ => false,
-    }
+    matches!(idx, 3 | 7 | 13)
 }
 
 // The special central rosetta, that allows one player to play again and
 // cannot be taken.
-const CENTRAL_ROSETTA: usize = 7;
+pub const CENTRAL_ROSETTA: usize = 7;
 
 // The special value used to represent a piece that enters the board.
 pub const ENTER: usize = 14;
 
-#[derive(Clone)]
+// The symbols of the pieces of each player.
+pub const SYMBOLS: [char; 2] = ['O', 'X'];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Board {
     // The number of pieces that have not been played for each player.
     pub ready: [u8; 2],
@@ -59,6 +63,111 @@ pub struct Board {
     pub turn: usize,
 }
 
+// The reasons why a board cannot be reached in a game, see `Board::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardError {
+    // The turn is neither 0 nor 1.
+    InvalidTurn(usize),
+    // A player does not have seven pieces in total.
+    PieceCount { player: usize, count: usize },
+    // Both players have a piece on the same central cell.
+    Overlap(usize),
+    // A player has all its pieces out of the board.
+    GameOver(usize),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BoardError::InvalidTurn(turn) =>
+                write!(f, "the turn should be 0 or 1, not {}", turn),
+            BoardError::PieceCount { player, count } =>
+                write!(f, "player {} has {} pieces instead of 7",
+                       SYMBOLS[player], count),
+            BoardError::Overlap(idx) =>
+                write!(f, "both players have a piece on the cell {}", idx),
+            BoardError::GameOver(player) =>
+                write!(f, "player {} has already won", SYMBOLS[player]),
+        }
+    }
+}
+
+// The reasons why a board notation cannot be read, see `Board::from_notation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotationError {
+    // The notation does not have the expected format.
+    Syntax,
+    // The notation is well-formed, but the board is not valid.
+    Board(BoardError),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::Syntax => write!(f, "invalid notation"),
+            NotationError::Board(e) => write!(f, "invalid board: {}", e),
+        }
+    }
+}
+
+// The result of a legal move, see `Board::try_perform_move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveOutcome {
+    // The other player is to play.
+    Played,
+    // The piece landed on a rosetta: the same player plays again.
+    PlayAgain,
+    // The player moved out its last piece and won the game.
+    Won,
+}
+
+// The reasons why a move is not legal, see `Board::check_move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMove {
+    // The player to play is neither 0 nor 1.
+    InvalidTurn(usize),
+    // A player has already won.
+    GameOver,
+    // The dice is not between 1 and 4.
+    InvalidDice(usize),
+    // The move is neither ENTER nor the index of a cell.
+    InvalidPlace(usize),
+    // There is no piece left to enter.
+    NoPieceReady,
+    // There is no piece of the player at this index.
+    NoPiece(usize),
+    // The piece would go beyond the exit: pieces move out with the exact dice.
+    Overshoot(usize),
+    // Another piece of the player is on the destination.
+    Blocked(usize),
+    // The opponent is on the central rosetta, where it cannot be taken.
+    CentralRosetta,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IllegalMove::InvalidTurn(turn) =>
+                write!(f, "the turn should be 0 or 1, not {}", turn),
+            IllegalMove::GameOver => write!(f, "the game is already over"),
+            IllegalMove::InvalidDice(dice) =>
+                write!(f, "no piece can move with a dice of {}", dice),
+            IllegalMove::InvalidPlace(place) =>
+                write!(f, "{} is not a cell of the board", place),
+            IllegalMove::NoPieceReady => write!(f, "no piece is left to enter"),
+            IllegalMove::NoPiece(place) =>
+                write!(f, "there is no piece to move on the cell {}", place),
+            IllegalMove::Overshoot(place) =>
+                write!(f, "the piece on the cell {} would go beyond the exit", place),
+            IllegalMove::Blocked(dest) =>
+                write!(f, "the cell {} is occupied by another piece of the player",
+                       dest),
+            IllegalMove::CentralRosetta =>
+                write!(f, "the opponent is on the central rosetta"),
+        }
+    }
+}
+
 // Default board: a new board.
 impl Default for Board {
     fn default() -> Self {
@@ -72,6 +181,39 @@ impl Default for Board {
 }
 
 impl Board {
+    // Builds a board, checking that it can be reached in a game that is not
+    // finished.
+    pub fn new(ready: [u8; 2], cells: [[bool; 14]; 2], out: [u8; 2], turn: usize)
+        -> Result<Board, BoardError> {
+        let board = Board { ready, cells, out, turn };
+        board.validate()?;
+        Ok(board)
+    }
+
+    // Checks that the board can be reached in a game that is not finished.
+    pub fn validate(&self) -> Result<(), BoardError> {
+        if self.turn > 1 {
+            return Err(BoardError::InvalidTurn(self.turn));
+        }
+        for player in 0..2 {
+            let on_board = self.cells[player].iter().filter(|&&piece| piece).count();
+            let count = self.ready[player] as usize + on_board +
+                self.out[player] as usize;
+            if count != 7 {
+                return Err(BoardError::PieceCount { player, count });
+            }
+        }
+        let shared = |&i: &usize| is_central(i) && self.cells[0][i] && self.cells[1][i];
+        if let Some(idx) = (0..14).find(shared) {
+            return Err(BoardError::Overlap(idx));
+        }
+        if let Some(player) = (0..2).find(|&player| self.out[player] == 7) {
+            return Err(BoardError::GameOver(player));
+        }
+
+        Ok(())
+    }
+
     pub fn disp(&self) {
         match self.turn {
             0 => println!("Turn: O"),
@@ -95,7 +237,7 @@ impl Board {
                 else { '.' };
             print!("{} ", symbol);
         }
-        println!("");
+        println!();
 
         // Displaying the common row:
         for i in 4..12 {
@@ -108,7 +250,7 @@ impl Board {
                 else { '.' };
             print!("{} ", symbol);
         }
-        println!("");
+        println!();
 
         // Displaying blue's row
         for i in (0..4).rev() {
@@ -179,11 +321,69 @@ impl Board {
         moves
     }
 
+    // Checks that moving the piece at index `place` (or ENTER) by `dice` cells
+    // is legal. The legal moves are the ones of `self.possible_moves(dice)`.
+    pub fn check_move(&self, dice: usize, place: usize) -> Result<(), IllegalMove> {
+        if self.turn > 1 {
+            return Err(IllegalMove::InvalidTurn(self.turn));
+        }
+        if self.out.contains(&7) {
+            return Err(IllegalMove::GameOver);
+        }
+        if dice == 0 || dice > 4 {
+            return Err(IllegalMove::InvalidDice(dice));
+        }
+        let dest = if place == ENTER {
+            if self.ready[self.turn] == 0 {
+                return Err(IllegalMove::NoPieceReady);
+            }
+            dice - 1
+        }
+        else if place < 14 {
+            if !self.cells[self.turn][place] {
+                return Err(IllegalMove::NoPiece(place));
+            }
+            place + dice
+        }
+        else {
+            return Err(IllegalMove::InvalidPlace(place));
+        };
+        if dest > 14 {
+            return Err(IllegalMove::Overshoot(place));
+        }
+        if dest < 14 && self.cells[self.turn][dest] {
+            return Err(IllegalMove::Blocked(dest));
+        }
+        if dest == CENTRAL_ROSETTA && self.cells[1 - self.turn][CENTRAL_ROSETTA] {
+            return Err(IllegalMove::CentralRosetta);
+        }
+
+        Ok(())
+    }
+
+    // Same as `perform_move`, but checks first that the move is legal (see
+    // `check_move`). The board is not changed if it is not.
+    pub fn try_perform_move(&mut self, dice: usize, place: usize)
+        -> Result<MoveOutcome, IllegalMove> {
+        self.check_move(dice, place)?;
+        let turn = self.turn;
+        if self.perform_move(dice, place) {
+            Ok(MoveOutcome::Won)
+        }
+        else if self.turn == turn {
+            Ok(MoveOutcome::PlayAgain)
+        }
+        else {
+            Ok(MoveOutcome::Played)
+        }
+    }
+
     // Moves the piece at index `place` by `dice` cells. The player that
     // is to play may change.
     //
     // This function does not check whether the input move is legal. Please
-    // ensure that this move comes from the vector `self.possible_moves(dice)`.
+    // ensure that this move comes from the vector `self.possible_moves(dice)`,
+    // or use `try_perform_move`.
     //
     // Returns true if this move is a winning move, false otherwise.
     pub fn perform_move(&mut self, dice: usize, place: usize) -> bool {
@@ -234,4 +434,329 @@ impl Board {
     pub fn finished(&self) -> bool {
         self.out[1 - self.turn] == 7
     }
+
+    // The notation of the board:
+    // "<turn> <ready O> <path O> <out O> <ready X> <path X> <out X>"
+    // where the turn is O or X, and each path gives its fourteen cells in the
+    // order of the indexes, with the symbol of the player for a piece and '.'
+    // for an empty cell. The notation of a new board is
+    // "O 7 .............. 0 7 .............. 0".
+    pub fn notation(&self) -> String {
+        let mut fields = vec![SYMBOLS[self.turn].to_string()];
+        for (player, &symbol) in SYMBOLS.iter().enumerate() {
+            let path: String = self.cells[player].iter()
+                .map(|&piece| if piece { symbol } else { '.' })
+                .collect();
+            fields.push(self.ready[player].to_string());
+            fields.push(path);
+            fields.push(self.out[player].to_string());
+        }
+
+        fields.join(" ")
+    }
+
+    // Reads the notation given by `notation`. Fails if it is not well-formed,
+    // or if the board is not valid (see `validate`).
+    pub fn from_notation(notation: &str) -> Result<Board, NotationError> {
+        let fields: Vec<&str> = notation.split_whitespace().collect();
+        if fields.len() != 7 {
+            return Err(NotationError::Syntax);
+        }
+        let turn = SYMBOLS.iter().position(|&c| fields[0] == c.to_string())
+            .ok_or(NotationError::Syntax)?;
+        let (mut ready, mut cells, mut out) = ([0; 2], [[false; 14]; 2], [0; 2]);
+        for player in 0..2 {
+            let fields = &fields[1 + 3 * player..4 + 3 * player];
+            ready[player] = fields[0].parse().map_err(|_| NotationError::Syntax)?;
+            let path: Vec<char> = fields[1].chars().collect();
+            if path.len() != 14 {
+                return Err(NotationError::Syntax);
+            }
+            for (cell, &c) in cells[player].iter_mut().zip(&path) {
+                *cell = match c {
+                    '.' => false,
+                    c if c == SYMBOLS[player] => true,
+                    _ => return Err(NotationError::Syntax),
+                };
+            }
+            out[player] = fields[2].parse().map_err(|_| NotationError::Syntax)?;
+        }
+
+        Board::new(ready, cells, out, turn).map_err(NotationError::Board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+
+    const NB_GAMES: u64 = 200;
+
+    // Plays random games, and calls `check` on each move with the board
+    // before the move, the dice, the move, and the board after the move.
+    fn random_games<F>(mut check: F)
+    where F: FnMut(&Board, usize, usize, &Board)
+    {
+        for seed in 0..NB_GAMES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut board = Board::default();
+            loop {
+                let dice = roll_dices(&mut rng);
+                let moves = board.possible_moves(dice);
+                if moves.is_empty() {
+                    board.change_turn();
+                    continue;
+                }
+                let place = *moves.choose(&mut rng).unwrap();
+                let before = board.clone();
+                let won = board.perform_move(dice, place);
+                check(&before, dice, place, &board);
+                if won {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn destination(dice: usize, place: usize) -> usize {
+        if place == ENTER { dice - 1 } else { place + dice }
+    }
+
+    // The test vectors of data/rules.txt.
+    const RULES_VECTORS: &str = include_str!("../data/rules.txt");
+
+    fn parse_moves(s: &str) -> Vec<usize> {
+        if s == "-" {
+            return Vec::new();
+        }
+        s.split_whitespace()
+            .map(|m| if m == "enter" { ENTER } else { m.parse().unwrap() })
+            .collect()
+    }
+
+    #[test]
+    fn rules_vectors() {
+        let mut board = None;
+        let mut nb_checks = 0;
+        for (i, line) in RULES_VECTORS.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let context = format!("data/rules.txt:{}: {}", i + 1, line);
+            let (command, args) = line.split_once(' ').expect(&context);
+            match command {
+                "position" => {
+                    let b = Board::from_notation(args).expect(&context);
+                    assert_eq!(b.notation(), args, "{}", context);
+                    board = Some(b);
+                }
+                "moves" => {
+                    let board = board.as_ref().expect(&context);
+                    let (dice, moves) = args.split_once(' ').expect(&context);
+                    let dice = dice.parse().expect(&context);
+                    assert_eq!(board.possible_moves(dice), parse_moves(moves),
+                               "{}", context);
+                    nb_checks += 1;
+                }
+                "play" => {
+                    let board = board.as_ref().expect(&context);
+                    let (played, expected) = args.split_once(" => ").expect(&context);
+                    let played = parse_moves(played);
+                    let (dice, place) = (played[0], played[1]);
+                    assert!(board.possible_moves(dice).contains(&place), "{}", context);
+                    let mut after = board.clone();
+                    let won = after.perform_move(dice, place);
+                    assert_eq!(after.notation(), expected, "{}", context);
+                    assert_eq!(won, after.out[after.turn] == 7, "{}", context);
+                    nb_checks += 1;
+                }
+                _ => panic!("{}: unknown command", context),
+            }
+        }
+        assert!(nb_checks > 0);
+    }
+
+    #[test]
+    fn check_move_agrees_with_possible_moves() {
+        random_games(|board, _, _, _| {
+            for dice in 0..=4 {
+                let moves = board.possible_moves(dice);
+                for place in 0..=15 {
+                    assert_eq!(board.check_move(dice, place).is_ok(),
+                               moves.contains(&place),
+                               "{} {} {}", board.notation(), dice, place);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn try_perform_move_outcomes() {
+        random_games(|before, dice, place, after| {
+            let mut board = before.clone();
+            let expected = if after.out[before.turn] == 7 {
+                MoveOutcome::Won
+            }
+            else if after.turn == before.turn {
+                MoveOutcome::PlayAgain
+            }
+            else {
+                MoveOutcome::Played
+            };
+            assert_eq!(board.try_perform_move(dice, place), Ok(expected));
+            assert_eq!(&board, after);
+        });
+
+        let mut board =
+            Board::from_notation("X 5 ...O.O........ 0 6 .......X...... 0").unwrap();
+        let before = board.clone();
+        assert_eq!(board.try_perform_move(0, ENTER), Err(IllegalMove::InvalidDice(0)));
+        assert_eq!(board.try_perform_move(1, 15), Err(IllegalMove::InvalidPlace(15)));
+        assert_eq!(board.try_perform_move(1, 3), Err(IllegalMove::NoPiece(3)));
+        board.turn = 0;
+        assert_eq!(board.try_perform_move(4, 3), Err(IllegalMove::CentralRosetta));
+        assert_eq!(board.try_perform_move(4, ENTER), Err(IllegalMove::Blocked(3)));
+        assert_eq!(board.try_perform_move(2, 3), Err(IllegalMove::Blocked(5)));
+        board.turn = 1;
+        assert_eq!(board, before);
+        assert_eq!(board.try_perform_move(4, 7), Ok(MoveOutcome::Played));
+        board.turn = 1;
+        assert_eq!(board.try_perform_move(4, 11), Err(IllegalMove::Overshoot(11)));
+        assert_eq!(board.try_perform_move(3, 11), Ok(MoveOutcome::Played));
+        board.ready = [0, 0];
+        assert_eq!(board.try_perform_move(1, ENTER), Err(IllegalMove::NoPieceReady));
+        board.out = [5, 7];
+        assert_eq!(board.try_perform_move(1, 5), Err(IllegalMove::GameOver));
+        board.turn = 2;
+        assert_eq!(board.try_perform_move(1, 5), Err(IllegalMove::InvalidTurn(2)));
+    }
+
+    #[test]
+    fn boards_of_games_are_valid() {
+        random_games(|_, _, _, board| {
+            let notation = board.notation();
+            if board.out[board.turn] == 7 {
+                assert_eq!(board.validate(), Err(BoardError::GameOver(board.turn)));
+                assert_eq!(Board::from_notation(&notation),
+                           Err(NotationError::Board(BoardError::GameOver(board.turn))));
+            }
+            else {
+                assert_eq!(board.validate(), Ok(()), "{}", notation);
+                assert_eq!(Board::from_notation(&notation).as_ref(), Ok(board));
+            }
+        });
+    }
+
+    #[test]
+    fn invalid_boards() {
+        let mut cells = [[false; 14]; 2];
+        assert_eq!(Board::new([7, 7], cells, [0, 0], 2),
+                   Err(BoardError::InvalidTurn(2)));
+        assert_eq!(Board::new([6, 7], cells, [0, 0], 0),
+                   Err(BoardError::PieceCount { player: 0, count: 6 }));
+        cells[1][13] = true;
+        assert_eq!(Board::new([7, 7], cells, [0, 0], 0),
+                   Err(BoardError::PieceCount { player: 1, count: 8 }));
+        cells[1][13] = false;
+        cells[0][5] = true;
+        cells[1][5] = true;
+        assert_eq!(Board::new([6, 6], cells, [0, 0], 1),
+                   Err(BoardError::Overlap(5)));
+        cells[1][5] = false;
+        assert_eq!(Board::new([6, 0], cells, [0, 7], 1),
+                   Err(BoardError::GameOver(1)));
+        assert!(Board::new([6, 7], cells, [0, 0], 1).is_ok());
+
+        assert_eq!(Board::from_notation("O 7 .............. 0 7 .............."),
+                   Err(NotationError::Syntax));
+        assert_eq!(Board::from_notation("O 7 ....X......... 0 7 .............. 0"),
+                   Err(NotationError::Syntax));
+        assert_eq!(Board::from_notation("X 7 ....O......... 0 7 .............. 0"),
+                   Err(NotationError::Board(
+                       BoardError::PieceCount { player: 0, count: 8 })));
+    }
+
+    #[test]
+    fn seven_pieces_per_player() {
+        random_games(|_, _, _, board| {
+            for player in 0..2 {
+                let on_board = board.cells[player].iter().filter(|&&c| c).count();
+                let total = board.ready[player] as usize + on_board +
+                    board.out[player] as usize;
+                assert_eq!(total, 7, "{}", board.notation());
+            }
+        });
+    }
+
+    #[test]
+    fn no_shared_central_cell() {
+        random_games(|_, _, _, board| {
+            for i in (0..14).filter(|&i| is_central(i)) {
+                assert!(!(board.cells[0][i] && board.cells[1][i]),
+                        "{}", board.notation());
+            }
+        });
+    }
+
+    #[test]
+    fn possible_moves_order() {
+        random_games(|board, _, _, _| {
+            for dice in 0..=4 {
+                let moves = board.possible_moves(dice);
+                let pieces: Vec<usize> =
+                    moves.iter().cloned().filter(|&m| m != ENTER).collect();
+                if pieces.len() < moves.len() {
+                    assert_eq!(moves[0], ENTER);
+                    assert_eq!(pieces.len(), moves.len() - 1);
+                }
+                assert!(pieces.windows(2).all(|w| w[0] < w[1]),
+                        "{} {:?}", board.notation(), moves);
+            }
+        });
+    }
+
+    #[test]
+    fn central_rosetta_is_safe() {
+        random_games(|before, dice, place, after| {
+            let adv = 1 - before.turn;
+            if before.cells[adv][CENTRAL_ROSETTA] {
+                assert_ne!(destination(dice, place), CENTRAL_ROSETTA);
+                assert!(after.cells[adv][CENTRAL_ROSETTA]);
+            }
+        });
+    }
+
+    #[test]
+    fn capture_returns_piece() {
+        random_games(|before, dice, place, after| {
+            let adv = 1 - before.turn;
+            let dest = destination(dice, place);
+            if dest < 14 && is_central(dest) && before.cells[adv][dest] {
+                assert!(!after.cells[adv][dest]);
+                assert_eq!(after.ready[adv], before.ready[adv] + 1);
+            }
+            else {
+                assert_eq!(after.ready[adv], before.ready[adv]);
+                assert_eq!(after.cells[adv], before.cells[adv]);
+            }
+        });
+    }
+
+    #[test]
+    fn rosetta_keeps_turn() {
+        random_games(|before, dice, place, after| {
+            let dest = destination(dice, place);
+            let won = after.out[before.turn] == 7;
+            if won || (dest < 14 && is_rosetta(dest)) {
+                assert_eq!(after.turn, before.turn);
+            }
+            else {
+                assert_eq!(after.turn, 1 - before.turn);
+            }
+        });
+    }
 }
@@ -0,0 +1,23 @@
+// The modules shared by the executables `main` and `training`.
+
+pub mod analysis;
+pub mod benchmark;
+pub mod book;
+pub mod calibration;
+pub mod checkpoint;
+pub mod database;
+pub mod features;
+pub mod game_manager;
+pub mod luck;
+pub mod mcts;
+pub mod monte_carlo;
+pub mod neural;
+pub mod players;
+pub mod record;
+pub mod reinforcement_learning;
+pub mod self_play;
+pub mod strategy;
+pub mod supervised;
+pub mod tablebase;
+pub mod tournament;
+pub mod weights;
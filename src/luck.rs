@@ -0,0 +1,121 @@
+use rand::thread_rng;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::tournament::*;
+use crate::record::*;
+use crate::calibration::Calibrated;
+
+// The luck of a dice roll is the difference between the value of the position
+// once the dice are known, and its expected value before the roll over
+// `PROBABILITIES`, for the player that rolls. The values are the equities of a
+// calibrated heuristic, 2p - 1 where p is a win probability, as in `analysis`:
+// they are in the same units as the results of the games, 1 for a win and -1
+// for a loss.
+//
+// The luck of each roll has an expectation of zero, so subtracting the luck of
+// the players from the result of a game keeps the expected result, but removes
+// much of the variance due to the dice.
+
+// The value of a position for the player to move once the dice are known, at
+// the given depth (at least 1).
+fn rolled_value<H: Heuristic>(h: &H, board: &Board, dice: usize, depth: u32) -> f32 {
+    let moves = board.possible_moves(dice);
+    if moves.is_empty() {
+        let mut copy = board.clone();
+        copy.change_turn();
+        return -expectimax(h, &copy, depth - 1);
+    }
+    best_move(h, board, dice, depth - 1).1
+}
+
+pub fn roll_luck<H>(h: &Calibrated<H>, board: &Board, dice: usize, depth: u32) -> f32
+where H: Heuristic
+{
+    let expected: f32 = (0..=4)
+        .map(|d| PROBABILITIES[d] * rolled_value(h, board, d, depth))
+        .sum();
+
+    rolled_value(h, board, dice, depth) - expected
+}
+
+// The luck of each player over a game, and its winner if it is finished.
+pub struct GameLuck {
+    pub luck: [f32; 2],
+    pub winner: Option<usize>,
+}
+
+impl GameLuck {
+    // The result of the game for player 0, 1 for a win and -1 for a loss, and
+    // the same result minus the luck of player 0 plus the luck of player 1.
+    // Returns None if the game is not finished.
+    pub fn results(&self) -> Option<(f32, f32)> {
+        let result = if self.winner? == 0 { 1.0 } else { -1.0 };
+        Some((result, result - self.luck[0] + self.luck[1]))
+    }
+}
+
+pub fn game_luck<H>(h: &Calibrated<H>, record: &Record, depth: u32) -> GameLuck
+where H: Heuristic
+{
+    let positions = record.positions();
+    let mut luck = [0.0; 2];
+    for (roll, board) in record.rolls.iter().zip(&positions) {
+        luck[board.turn] += roll_luck(h, board, roll.dice, depth);
+    }
+
+    GameLuck { luck, winner: record.winner() }
+}
+
+// The results of a showdown, as rates of games won by the first player, with
+// their standard errors.
+pub struct LuckReport {
+    pub games: u32,
+    pub score: f32,
+    pub error: f32,
+    // The same once the luck is removed.
+    pub adjusted: f32,
+    pub adjusted_error: f32,
+}
+
+// The mean of the results converted to a rate of games won, with its standard
+// error.
+fn rate(results: &[f32]) -> (f32, f32) {
+    let n = results.len() as f32;
+    let mean = results.iter().sum::<f32>() / n;
+    let var = results.iter().map(|r| (r - mean) * (r - mean)).sum::<f32>() / (n - 1.0);
+
+    ((mean + 1.0) / 2.0, (var / n).sqrt() / 2.0)
+}
+
+// Plays 2 * nb_games games between two players as `showdown` does, and
+// measures the luck of each game with `h` at the given depth.
+pub fn luck_adjusted_showdown<H: Heuristic>(
+    mut player_a: impl Player,
+    mut player_b: impl Player,
+    nb_games: u32,
+    h: &Calibrated<H>,
+    depth: u32
+) -> LuckReport {
+    let mut rng = thread_rng();
+    let mut results = Vec::new();
+    let mut adjusted = Vec::new();
+    for i in 0..2 * nb_games {
+        // Player a plays first in one game out of two.
+        let start = Board::default();
+        let (record, sign) = if i % 2 == 0 {
+            (Record::play(start, &mut player_a, &mut player_b, &mut rng), 1.0)
+        }
+        else {
+            (Record::play(start, &mut player_b, &mut player_a, &mut rng), -1.0)
+        };
+        let (result, adjusted_result) = game_luck(h, &record, depth).results()
+            .expect("The game should be finished");
+        results.push(sign * result);
+        adjusted.push(sign * adjusted_result);
+    }
+    let (score, error) = rate(&results);
+    let (adjusted, adjusted_error) = rate(&adjusted);
+
+    LuckReport { games: 2 * nb_games, score, error, adjusted, adjusted_error }
+}
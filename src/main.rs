@@ -1,77 +1,222 @@
 extern crate rand;
-use rand::{Rng, thread_rng};
-use rand::seq::SliceRandom;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use rand::thread_rng;
 
-mod game_manager;
-use game_manager::*;
+use ur::game_manager::*;
+use ur::strategy::*;
+use ur::tournament::*;
+use ur::reinforcement_learning::*;
+use ur::players::*;
+use ur::mcts::*;
+use ur::monte_carlo::*;
+use ur::calibration::*;
+use ur::neural::*;
+use ur::weights::*;
+use ur::record::*;
+use ur::analysis::*;
+use ur::luck::*;
+use ur::book::*;
+use ur::tablebase::*;
+use ur::database::*;
 
-mod strategy;
-use strategy::*;
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        None => tournament(),
+        Some("calibrate") => calibrate_heuristics(),
+        Some("depth-units") => compare_depth_units(),
+        Some("quiescence") => compare_quiescence(),
+        Some("mlp") => match args.get(2) {
+            Some(path) => compare_mlp(path),
+            None => eprintln!("Usage: main mlp <weight file>"),
+        },
+        Some("analyse") => analyse_command(&args[2..]),
+        Some("annotate") => annotate_command(&args[2..]),
+        Some("luck") => match args.get(2) {
+            Some(path) => luck_command(path),
+            None => eprintln!("Usage: main luck <record file>"),
+        },
+        Some("luck-adjusted") => compare_luck_adjusted(),
+        Some("book") => book_command(&args[2..]),
+        Some("tablebase") => tablebase_command(&args[2..]),
+        Some("collect") => match args.get(2) {
+            Some(path) => collect_positions(path, args.get(3)),
+            None => eprintln!("Usage: main collect <database> [number of games]"),
+        },
+        Some("query") => match args.get(2) {
+            Some(path) => query_position(path, &args[3..].join(" ")),
+            None => eprintln!("Usage: main query <database> <notation>"),
+        },
+        Some("record") => match args.get(2) {
+            Some(path) => record_game(path),
+            None => eprintln!("Usage: main record <record file>"),
+        },
+        Some(cmd) => eprintln!("Unknown command: {}", cmd),
+    }
+}
 
-mod tournament;
-use tournament::*;
+// The weight file of the LinearEval0 found by `training`, relative to the
+// working directory.
+const LINEAR_EVAL0_PATH: &str = "weights/linear_eval0.txt";
 
-mod reinforcement_learning;
-use reinforcement_learning::*;
+fn linear_eval() -> LinearEval0 {
+    load(LINEAR_EVAL0_PATH).expect("Cannot read the linear evaluation").0
+}
 
-struct LastMove {}
-impl Player for LastMove {
-    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
-        *board.possible_moves(dice).last().unwrap()
-    }
+// The number of self-play games over which the heuristics are calibrated.
+const CALIBRATION_GAMES: u32 = 2000;
+
+// The heuristic `h` with its calibration, see `Calibrated`.
+fn calibrated<H: Heuristic>(h: H) -> Calibrated<H> {
+    let logistic = calibrate(&h, CALIBRATION_GAMES, &mut thread_rng());
+    Calibrated { h, logistic }
 }
 
-struct RandMove<R: Rng> {
-    rng: R,
+// The calibration of the linear evaluation, fitted at the first call and
+// written to the working directory.
+const LINEAR_CALIBRATION_PATH: &str = "linear_eval0.calibration";
+
+// The linear evaluation with its calibration. The calibration is read from
+// `LINEAR_CALIBRATION_PATH`, or fitted and written there if the file does not
+// exist. Remove the file after changing the weights.
+fn calibrated_linear() -> Calibrated<LinearEval0> {
+    let logistic = match Logistic::load(LINEAR_CALIBRATION_PATH) {
+        Ok(logistic) => logistic,
+        Err(_) => {
+            let mut rng = thread_rng();
+            let logistic = calibrate(&linear_eval(), CALIBRATION_GAMES, &mut rng);
+            if let Err(e) = logistic.save(LINEAR_CALIBRATION_PATH) {
+                eprintln!("Cannot write the calibration: {}", e);
+            }
+            logistic
+        }
+    };
+    Calibrated { h: linear_eval(), logistic }
 }
-impl<R: Rng> Player for RandMove<R> {
-    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
-        *board.possible_moves(dice).choose(&mut self.rng).unwrap()
+
+// Fits a win probability to the heuristics over self-play games, and prints
+// the win probability of the first player on some boards.
+fn calibrate_heuristics() {
+    let mut rng = thread_rng();
+    let simple = Calibrated {
+        logistic: calibrate(&SimpleHeuristic {}, CALIBRATION_GAMES, &mut rng),
+        h: SimpleHeuristic {},
+    };
+    let linear = Calibrated {
+        logistic: calibrate(&linear_eval(), CALIBRATION_GAMES, &mut rng),
+        h: linear_eval(),
+    };
+    println!("Advancement: scale = {}, bias = {}",
+             simple.logistic.scale, simple.logistic.bias);
+    println!("Linear     : scale = {}, bias = {}\n",
+             linear.logistic.scale, linear.logistic.bias);
+
+    let mut board = Board::default();
+    board.disp();
+    println!("Advancement: P(win) = {}", simple.win_probability(&board));
+    println!("Linear     : P(win) = {}\n", linear.win_probability(&board));
+
+    for (dice, place) in [(4, ENTER), (3, 3), (2, ENTER), (4, ENTER)] {
+        board.perform_move(dice, place);
     }
+    board.disp();
+    println!("Advancement: P(win) = {}", simple.win_probability(&board));
+    println!("Linear     : P(win) = {}", linear.win_probability(&board));
 }
 
-struct Greedy {}
-impl Player for Greedy {
-    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
-        let moves = board.possible_moves(dice);
-        let adv = 1 - board.turn;
-        for &place in moves.iter().rev() {
-            if is_central(place + dice) && board.cells[adv][place + dice] {
-                return place;
-            }
-        }
-        for &place in moves.iter().rev() {
-            if is_rosetta(place + dice) || (place == ENTER && dice == 4) {
-                return place;
-            }
-        }
-        *moves.last().unwrap()
+// Runs a showdown between two players and prints the results with the time
+// taken.
+fn compare(
+    name_a: &str,
+    player_a: impl Player,
+    name_b: &str,
+    player_b: impl Player,
+    nb_games: u32
+) {
+    let start = Instant::now();
+    let res = showdown(player_a, player_b, nb_games);
+    println!("{}: {}/{}", name_a, res[0], 2 * nb_games);
+    println!("{}: {}/{}", name_b, res[1], 2 * nb_games);
+    println!("({:.1}s)\n", start.elapsed().as_secs_f32());
+}
+
+// Compares expectimax players whose depth is counted in dice rolls with
+// players whose depth is counted in turns, at the same nominal depth.
+fn compare_depth_units() {
+    for depth in 1..=3 {
+        let rolls = ExpectimaxPlayer {
+            h: SimpleHeuristic {},
+            depth,
+            options: SearchOptions {
+                depth_unit: DepthUnit::Rolls,
+                ..SearchOptions::default()
+            },
+        };
+        let turns = ExpectimaxPlayer {
+            h: SimpleHeuristic {},
+            depth,
+            options: SearchOptions {
+                depth_unit: DepthUnit::Turns,
+                ..SearchOptions::default()
+            },
+        };
+        println!("Depth {}", depth);
+        compare("Rolls", rolls, "Turns", turns, 250);
     }
 }
 
-struct ExpectimaxPlayer<H: Heuristic> {
-    h: H,
-    depth: u32,
+// Compares plain expectimax players with players using a quiescence search,
+// at the same depth.
+fn compare_quiescence() {
+    for depth in 2..=3 {
+        let plain = ExpectimaxPlayer {
+            h: linear_eval(),
+            depth,
+            options: SearchOptions::default(),
+        };
+        let quiescence = ExpectimaxPlayer {
+            h: linear_eval(),
+            depth,
+            options: SearchOptions {
+                quiescence: 2,
+                ..SearchOptions::default()
+            },
+        };
+        println!("Depth {}", depth);
+        compare("Plain     ", plain, "Quiescence", quiescence, 250);
+    }
 }
 
-impl<H: Heuristic> Player for ExpectimaxPlayer<H> {
-    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
-        let moves = board.possible_moves(dice);
-        let mut best = moves[0];
-        let mut best_val = eval_move(&self.h, board, dice, moves[0], self.depth - 1);
-        for &place in moves.iter().skip(1) {
-            let val = eval_move(&self.h, board, dice, place, self.depth - 1);
-            if val > best_val {
-                best_val = val;
-                best = place;
-            }
+// Compares a network written by `training td-lambda mlp` with the linear
+// evaluation.
+fn compare_mlp(path: &str) {
+    let mlp: Mlp = match load(path) {
+        Ok((mlp, _)) => mlp,
+        Err(e) => {
+            eprintln!("Cannot read the network: {}", e);
+            return;
         }
-
-        best
+    };
+    for depth in 1..=2 {
+        let mlp_player = ExpectimaxPlayer {
+            h: mlp.clone(),
+            depth,
+            options: SearchOptions::default(),
+        };
+        let linear_player = ExpectimaxPlayer {
+            h: linear_eval(),
+            depth,
+            options: SearchOptions::default(),
+        };
+        println!("Depth {}", depth);
+        compare("MLP   ", mlp_player, "Linear", linear_player, 500);
     }
 }
 
-fn main() {
+fn tournament() {
     let rand_move = RandMove {rng: thread_rng()};
     let last_move = LastMove {};
     let res = showdown(rand_move, last_move, 500);
@@ -84,78 +229,476 @@ fn main() {
     println!("Greedy   : {}/1000", res[0]);
     println!("Last move: {}/1000\n", res[1]);
 
-    let linear_eval = LinearEval0 {
-        val_ready: -0.11500916,
-        val_cells: [
-           -0.07836597,
-           -0.06630784,
-           -0.053295016,
-           -0.06753837,
-           -0.054491982,
-           -0.053376794,
-           -0.036632307,
-           0.054832537,
-           0.006475265,
-           0.02226811,
-           0.044687957,
-           0.07274747,
-           0.112757705,
-           0.085373685],
-        val_out: 0.12581697,
-        player_adv: 0.027448557
-    };
-
     let advancement = ExpectimaxPlayer {
         h: SimpleHeuristic {},
         depth: 4,
+        options: SearchOptions::default(),
     };
-    let linear_player = ExpectimaxPlayer {
-        h: linear_eval,
-        depth: 4,
-    };
+    let linear_player: ExpectimaxPlayer<LinearEval0> =
+        ExpectimaxPlayer::load(LINEAR_EVAL0_PATH, 4)
+        .expect("Cannot read the linear evaluation");
     let res = showdown(advancement, linear_player, 500);
     println!("Advancement: {}/1000", res[0]);
-    println!("Linear     : {}/1000", res[1]);
-    // TODO: move in test module
-    /*
-    let mut board = Board {
-        ready: [1, 0],
-        cells: [[false; 14]; 2],
-        out: [2, 1],
-        turn: 1,
+    println!("Linear     : {}/1000\n", res[1]);
+
+    let mcts = MctsPlayer {
+        rollout: RandMove {rng: thread_rng()},
+        budget: Budget::Time(Duration::from_millis(20)),
+        exploration: 2.0f32.sqrt(),
+        rng: thread_rng(),
     };
-    for &i in &[1, 3, 5, 10] {
-        board.cells[0][i] = true;
+    let advancement = ExpectimaxPlayer {
+        h: SimpleHeuristic {},
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let res = showdown(mcts, advancement, 100);
+    println!("MCTS       : {}/200", res[0]);
+    println!("Advancement: {}/200\n", res[1]);
+
+    let monte_carlo = MonteCarloPlayer::seeded(RolloutPolicy::Greedy, 100, 4, 0);
+    let advancement = ExpectimaxPlayer {
+        h: SimpleHeuristic {},
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let res = showdown(monte_carlo, advancement, 100);
+    println!("Monte Carlo: {}/200", res[0]);
+    println!("Advancement: {}/200", res[1]);
+}
+
+// Plays a game between expectimax players using the linear evaluation and the
+// advancement heuristic, and writes its record.
+fn record_game(path: &str) {
+    let mut linear = ExpectimaxPlayer {
+        h: linear_eval(),
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let mut advancement = ExpectimaxPlayer {
+        h: SimpleHeuristic {},
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let record = Record::play(Board::default(), &mut linear, &mut advancement,
+                              &mut thread_rng());
+    if let Err(e) = record.save(path) {
+        eprintln!("Cannot write the record: {}", e);
     }
-    for &i in &[0, 1, 3, 7, 12, 13] {
-        board.cells[1][i] = true;
+}
+
+// Usage:
+// main analyse <notation> [--depth <depth>] [--heuristic linear | advancement]
+// main analyse --record <record file> [--roll <number>] [--depth <depth>]
+//              [--heuristic linear | advancement]
+//
+// Prints the values of the moves of a position for each dice roll, given by
+// its notation (see `Board::notation`) or as the position of a game record
+// before the given roll (1 for the starting position, by default the last
+// roll).
+fn analyse_command(args: &[String]) {
+    let notation: Vec<&str> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .map(|a| a.as_str())
+        .collect();
+    let mut record = None;
+    let mut roll = None;
+    let mut depth = 2;
+    let mut heuristic = "linear".to_string();
+
+    let mut options = args.iter().skip(notation.len());
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("Missing value for {}", option);
+                return;
+            }
+        };
+        match option.as_str() {
+            "--record" => match Record::load(value) {
+                Ok(r) => record = Some(r),
+                Err(e) => {
+                    eprintln!("Cannot read the record: {}", e);
+                    return;
+                }
+            },
+            "--roll" => match value.parse::<usize>() {
+                Ok(n) if n >= 1 => roll = Some(n),
+                _ => {
+                    eprintln!("Invalid roll: {}", value);
+                    return;
+                }
+            },
+            "--depth" => match value.parse() {
+                Ok(d) if d >= 1 => depth = d,
+                _ => {
+                    eprintln!("Invalid depth: {}", value);
+                    return;
+                }
+            },
+            "--heuristic" => heuristic = value.clone(),
+            _ => {
+                eprintln!("Unknown option: {}", option);
+                return;
+            }
+        }
     }
+
+    let board = match record {
+        Some(record) => {
+            let positions = record.positions();
+            match positions.get(roll.unwrap_or(record.rolls.len().max(1)) - 1) {
+                Some(board) => board.clone(),
+                None => {
+                    eprintln!("The game has only {} rolls", record.rolls.len());
+                    return;
+                }
+            }
+        }
+        None => match Board::from_notation(&notation.join(" ")) {
+            Ok(board) => board,
+            Err(e) => {
+                eprintln!("Invalid position {}: {}", notation.join(" "), e);
+                return;
+            }
+        },
+    };
+
     board.disp();
+    println!("{}\n", board.notation());
+    match heuristic.as_str() {
+        "linear" => analyse(&linear_eval(), &board, depth).disp(),
+        "advancement" => analyse(&SimpleHeuristic {}, &board, depth).disp(),
+        h => eprintln!("Unknown heuristic: {}", h),
+    }
+}
 
-    let h = SimpleHeuristic {};
-    println!("Evaluation: {}", h.eval(&board));
+// Usage:
+// main annotate <record file> [--depth <depth>]
+//               [--heuristic linear | advancement]
+//
+// Prints the equity lost by each move of a game record, and the accuracy of
+// each player.
+fn annotate_command(args: &[String]) {
+    let record = match args.first().map(|path| Record::load(path)) {
+        Some(Ok(record)) => record,
+        Some(Err(e)) => {
+            eprintln!("Cannot read the record: {}", e);
+            return;
+        }
+        None => {
+            eprintln!("Usage: main annotate <record file> [--depth <depth>] \
+                       [--heuristic linear | advancement]");
+            return;
+        }
+    };
+    let mut depth = 2;
+    let mut heuristic = "linear".to_string();
 
-    for dice in 0..=4 {
-        println!("Dice {}", dice);
-        for &place in board.possible_moves(dice).iter() {
-            let val = eval_move(&h, &board, dice, place, 0);
-            println!("  move {}: val = {}", place, val);
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("Missing value for {}", option);
+                return;
+            }
+        };
+        match option.as_str() {
+            "--depth" => match value.parse() {
+                Ok(d) if d >= 1 => depth = d,
+                _ => {
+                    eprintln!("Invalid depth: {}", value);
+                    return;
+                }
+            },
+            "--heuristic" => heuristic = value.clone(),
+            _ => {
+                eprintln!("Unknown option: {}", option);
+                return;
+            }
         }
     }
-    for i in 0..=5 {
-        println!("Eval at depth {}: {}", i, expectimax(&h, &board, i));
+
+    // The heuristics are calibrated for their equities to be comparable.
+    match heuristic.as_str() {
+        "linear" => {
+            disp_annotations(&annotate(&calibrated_linear(), &record, depth))
+        }
+        "advancement" => {
+            disp_annotations(&annotate(&calibrated(SimpleHeuristic {}), &record, depth))
+        }
+        h => eprintln!("Unknown heuristic: {}", h),
+    }
+}
+
+// Prints the luck of each player of a game record, as measured by the
+// calibrated linear evaluation at depth 1.
+fn luck_command(path: &str) {
+    let record = match Record::load(path) {
+        Ok(record) => record,
+        Err(e) => {
+            eprintln!("Cannot read the record: {}", e);
+            return;
+        }
+    };
+    let luck = game_luck(&calibrated_linear(), &record, 1);
+    for (player, luck) in luck.luck.iter().enumerate() {
+        println!("Player {}: luck {:.3}", SYMBOLS[player], luck);
+    }
+    match luck.results() {
+        Some((result, adjusted)) => {
+            println!("Result for O: {}, luck-adjusted: {:.3}", result, adjusted);
+        }
+        None => println!("The game is not finished"),
+    }
+}
+
+// Compares the linear evaluation with the advancement heuristic, with and
+// without the luck of the games.
+fn compare_luck_adjusted() {
+    let linear = ExpectimaxPlayer {
+        h: linear_eval(),
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let advancement = ExpectimaxPlayer {
+        h: SimpleHeuristic {},
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let start = Instant::now();
+    let h = calibrated_linear();
+    let report = luck_adjusted_showdown(linear, advancement, 250, &h, 1);
+    println!("Linear against Advancement over {} games:", report.games);
+    println!("Score         : {:.3} ± {:.3}", report.score, report.error);
+    println!("Luck-adjusted : {:.3} ± {:.3}", report.adjusted, report.adjusted_error);
+    println!("({:.1}s)", start.elapsed().as_secs_f32());
+}
+
+// Usage:
+// main book <book file> [--rolls <number of rolls>] [--depth <depth>]
+//
+// Reads an opening book, or builds it with the linear evaluation if the file
+// does not exist, and compares a depth-2 expectimax player using it with the
+// same player without it.
+fn book_command(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: main book <book file> [--rolls <number of rolls>] \
+                       [--depth <depth>]");
+            return;
+        }
+    };
+    let mut rolls = 6;
+    let mut depth = 4;
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        let value = match options.next().map(|v| v.parse::<u32>()) {
+            Some(Ok(value)) => value,
+            _ => {
+                eprintln!("Missing or invalid value for {}", option);
+                return;
+            }
+        };
+        match option.as_str() {
+            "--rolls" if value <= MAX_ROLLS => rolls = value,
+            "--depth" if value >= 1 => depth = value,
+            _ => {
+                eprintln!("Invalid option: {} {}", option, value);
+                return;
+            }
+        }
+    }
+
+    let book = if Path::new(path).exists() {
+        match Book::load(path) {
+            Ok(book) => book,
+            Err(e) => {
+                eprintln!("Cannot read the book: {}", e);
+                return;
+            }
+        }
+    }
+    else {
+        let start = Instant::now();
+        let book = Book::build(&linear_eval(), rolls, depth);
+        println!("Built a book of {} positions ({:.1}s)",
+                 book.moves.len(), start.elapsed().as_secs_f32());
+        if let Err(e) = book.save(path) {
+            eprintln!("Cannot write the book: {}", e);
+            return;
+        }
+        book
+    };
+    println!("{} positions, {} rolls, depth {}\n", book.moves.len(), book.rolls,
+             book.depth);
+
+    let book_player = BookPlayer::new(book, ExpectimaxPlayer {
+        h: linear_eval(),
+        depth: 2,
+        options: SearchOptions::default(),
+    });
+    let linear_player = ExpectimaxPlayer {
+        h: linear_eval(),
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    compare("Book  ", book_player, "Linear", linear_player, 500);
+}
+
+// Usage:
+// main tablebase <tablebase file> [--pieces <number of pieces>]
+//
+// Reads an endgame tablebase, or generates it if the file does not exist, and
+// compares a depth-2 expectimax player using it with the same player without
+// it, both using the calibrated linear evaluation.
+fn tablebase_command(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: main tablebase <tablebase file> \
+                       [--pieces <number of pieces>]");
+            return;
+        }
+    };
+    let pieces = match (args.get(1).map(|a| a.as_str()), args.get(2)) {
+        (None, _) => 3,
+        (Some("--pieces"), Some(value)) => match value.parse() {
+            Ok(pieces) if (1..=MAX_PIECES).contains(&pieces) => pieces,
+            _ => {
+                eprintln!("Invalid number of pieces: {}", value);
+                return;
+            }
+        },
+        _ => {
+            eprintln!("Usage: main tablebase <tablebase file> \
+                       [--pieces <number of pieces>]");
+            return;
+        }
+    };
+
+    let table = if Path::new(path).exists() {
+        match Tablebase::load(path) {
+            Ok(table) => table,
+            Err(e) => {
+                eprintln!("Cannot read the tablebase: {}", e);
+                return;
+            }
+        }
+    }
+    else {
+        let start = Instant::now();
+        let table = Tablebase::generate(pieces);
+        println!("Solved {} positions ({:.1}s)", table.nb_positions(),
+                 start.elapsed().as_secs_f32());
+        if let Err(e) = table.save(path) {
+            eprintln!("Cannot write the tablebase: {}", e);
+            return;
+        }
+        table
+    };
+    println!("{} pieces, {} positions\n", table.pieces, table.nb_positions());
+
+    // The exact values are win probabilities, so the linear evaluation is
+    // calibrated to be comparable with them.
+    let tablebase_player = ExpectimaxPlayer {
+        h: Tablebased {
+            h: calibrated_linear(),
+            table: Arc::new(table),
+        },
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let linear_player = ExpectimaxPlayer {
+        h: calibrated_linear(),
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    compare("Tablebase", tablebase_player, "Linear   ", linear_player, 500);
+}
+
+// Adds the positions of a showdown between expectimax players using the linear
+// evaluation and the advancement heuristic to a position database.
+fn collect_positions(path: &str, nb_games: Option<&String>) {
+    let nb_games = match nb_games.map(|n| n.parse()) {
+        None => 500,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("Invalid number of games");
+            return;
+        }
+    };
+    let mut db = match Database::load(path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Cannot read the database: {}", e);
+            return;
+        }
+    };
+    let linear = ExpectimaxPlayer {
+        h: linear_eval(),
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let advancement = ExpectimaxPlayer {
+        h: SimpleHeuristic {},
+        depth: 2,
+        options: SearchOptions::default(),
+    };
+    let res = showdown_observed(linear, advancement, nb_games, |record| {
+        db.add_record(record)
+    });
+    println!("Linear     : {}/{}", res[0], 2 * nb_games);
+    println!("Advancement: {}/{}", res[1], 2 * nb_games);
+    println!("{} positions", db.positions.len());
+    if let Err(e) = db.save(path) {
+        eprintln!("Cannot write the database: {}", e);
     }
+}
 
-    // Printing evaluation for the other player
-    println!("\nSwitching");
-    board.turn = 0;
-    for dice in 0..=4 {
+// Prints the statistics of a position of a database, given by its notation.
+fn query_position(path: &str, notation: &str) {
+    let board = match Board::from_notation(notation) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("Invalid position {}: {}", notation, e);
+            return;
+        }
+    };
+    let db = match Database::load(path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Cannot read the database: {}", e);
+            return;
+        }
+    };
+    board.disp();
+    let stats = match db.positions.get(&board) {
+        Some(stats) => stats,
+        None => {
+            println!("Position not found");
+            return;
+        }
+    };
+    println!("Seen {} times, won {:.1}% by the player to play", stats.seen,
+             100.0 * stats.win_rate());
+    for dice in 1..=4 {
+        let mut moves: Vec<&MoveStats> =
+            stats.moves.iter().filter(|m| m.dice == dice).collect();
+        if moves.is_empty() {
+            continue;
+        }
+        moves.sort_by_key(|m| std::cmp::Reverse(m.played));
+        let best = stats.best_move(dice).map(|m| m.place);
         println!("Dice {}", dice);
-        for &place in board.possible_moves(dice).iter() {
-            let val = eval_move(&h, &board, dice, place, 0);
-            println!("  move {}: val = {}", place, val);
+        for m in moves {
+            let mark = if best == Some(m.place) { " (best known)" } else { "" };
+            println!("  {:<12} played {} times, won {:.1}%{}",
+                     move_description(dice, m.place), m.played,
+                     100.0 * m.wins as f32 / m.played as f32, mark);
         }
     }
-    println!("Eval at depth 1: {}", expectimax(&h, &board, 1));
-    */
 }
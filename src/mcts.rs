@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant};
+use rand::Rng;
+
+use crate::game_manager::*;
+use crate::tournament::*;
+
+// Monte Carlo Tree Search (UCT) with explicit chance nodes for the dices.
+//
+// The tree alternates between two kinds of nodes:
+// - decision nodes, where the dice is known and the player to play chooses a
+//   move with the UCB1 formula;
+// - chance nodes, reached after a move, where the next dice is sampled with
+//   the real probabilities of `PROBABILITIES`. A dice that gives no legal move
+//   leads directly to the chance node of the opponent.
+// Rosetta landings are handled naturally: the decision node reached after the
+// dice roll belongs to whoever has to play, which may be the same player.
+//
+// Leaves are evaluated by playing the game until the end with a rollout
+// policy, which can be any `Player`: `RandMove` for plain random rollouts, or
+// an `ExpectimaxPlayer` of depth 1 for rollouts guided by a heuristic.
+
+// When to stop searching.
+#[derive(Clone, Copy)]
+pub enum Budget {
+    // A fixed number of iterations per move.
+    Iterations(u32),
+    // A fixed thinking time per move.
+    Time(Duration),
+}
+
+pub struct MctsPlayer<P: Player, R: Rng> {
+    // The policy used to finish the games during the simulations.
+    pub rollout: P,
+    pub budget: Budget,
+    // The exploration constant of UCB1. The classical value is sqrt(2).
+    pub exploration: f32,
+    pub rng: R,
+}
+
+enum Kind {
+    // The dice is known and `moves` are the legal moves. `children[i]` is the
+    // node reached by playing `moves[i]`; moves are expanded in order.
+    Decision { dice: usize, moves: Vec<usize>, children: Vec<usize> },
+    // The next dice is going to be rolled. `children[dice]` is the node
+    // reached after rolling `dice`, if it has been visited.
+    Chance { children: [Option<usize>; 5] },
+    // The game is over.
+    Terminal { winner: usize },
+}
+
+struct Node {
+    board: Board,
+    kind: Kind,
+    visits: u32,
+    // The number of simulations won by the root player that went through this
+    // node.
+    wins: f32,
+}
+
+// The search tree, stored as an arena of nodes. The root is the node 0.
+struct Tree {
+    nodes: Vec<Node>,
+    // The player to play at the root.
+    root_player: usize,
+}
+
+impl Tree {
+    fn new(board: &Board, dice: usize) -> Tree {
+        let root = Node {
+            board: board.clone(),
+            kind: Kind::Decision {
+                dice,
+                moves: board.possible_moves(dice),
+                children: Vec::new(),
+            },
+            visits: 0,
+            wins: 0.0,
+        };
+        Tree { nodes: vec![root], root_player: board.turn }
+    }
+
+    fn push(&mut self, board: Board, kind: Kind) -> usize {
+        self.nodes.push(Node { board, kind, visits: 0, wins: 0.0 });
+        self.nodes.len() - 1
+    }
+
+    // Returns the child of a fully expanded decision node that maximizes the
+    // UCB1 value for the player to play at this node.
+    fn select(&self, idx: usize, exploration: f32) -> usize {
+        let node = &self.nodes[idx];
+        let children = match &node.kind {
+            Kind::Decision { children, .. } => children,
+            _ => unreachable!(),
+        };
+        let log_visits = (node.visits as f32).ln();
+        let mut best = children[0];
+        let mut best_val = f32::NEG_INFINITY;
+        for &child in children {
+            let c = &self.nodes[child];
+            let mut rate = c.wins / c.visits as f32;
+            if node.board.turn != self.root_player {
+                rate = 1.0 - rate;
+            }
+            let val = rate + exploration * (log_visits / c.visits as f32).sqrt();
+            if val > best_val {
+                best_val = val;
+                best = child;
+            }
+        }
+
+        best
+    }
+
+    // Runs one simulation: selection, expansion, rollout and backpropagation.
+    fn iterate<P, R>(&mut self, rollout: &mut P, exploration: f32, rng: &mut R)
+    where P: Player, R: Rng
+    {
+        let mut path = vec![0];
+        let mut idx = 0;
+        let winner = loop {
+            match &self.nodes[idx].kind {
+                Kind::Terminal { winner } => break *winner,
+                Kind::Decision { dice, moves, children } => {
+                    if children.len() < moves.len() {
+                        // Expansion of the next unexplored move.
+                        let place = moves[children.len()];
+                        let dice = *dice;
+                        let mut board = self.nodes[idx].board.clone();
+                        let kind = if board.perform_move(dice, place) {
+                            Kind::Terminal { winner: board.turn }
+                        }
+                        else {
+                            Kind::Chance { children: [None; 5] }
+                        };
+                        let child = self.push(board, kind);
+                        if let Kind::Decision { children, .. } =
+                            &mut self.nodes[idx].kind {
+                            children.push(child);
+                        }
+                        path.push(child);
+                        let node = &self.nodes[child];
+                        break match node.kind {
+                            Kind::Terminal { winner } => winner,
+                            // A rollout policy that plays an illegal move
+                            // loses the game.
+                            _ => play_out(&node.board, rollout, rng)
+                                .unwrap_or_else(|forfeit| 1 - forfeit.player),
+                        };
+                    }
+                    idx = self.select(idx, exploration);
+                    path.push(idx);
+                }
+                Kind::Chance { children } => {
+                    let dice = roll_dices(rng);
+                    idx = match children[dice] {
+                        Some(child) => child,
+                        None => self.expand_chance(idx, dice),
+                    };
+                    path.push(idx);
+                }
+            }
+        };
+
+        let result = if winner == self.root_player { 1.0 } else { 0.0 };
+        for &i in &path {
+            self.nodes[i].visits += 1;
+            self.nodes[i].wins += result;
+        }
+    }
+
+    // Creates the node reached from the chance node `idx` after rolling
+    // `dice`.
+    fn expand_chance(&mut self, idx: usize, dice: usize) -> usize {
+        let mut board = self.nodes[idx].board.clone();
+        let moves = board.possible_moves(dice);
+        let child = if moves.is_empty() {
+            board.change_turn();
+            self.push(board, Kind::Chance { children: [None; 5] })
+        }
+        else {
+            self.push(board, Kind::Decision { dice, moves, children: Vec::new() })
+        };
+        if let Kind::Chance { children } = &mut self.nodes[idx].kind {
+            children[dice] = Some(child);
+        }
+
+        child
+    }
+}
+
+impl<P: Player, R: Rng> Player for MctsPlayer<P, R> {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        let mut tree = Tree::new(board, dice);
+        match self.budget {
+            Budget::Iterations(n) => {
+                for _ in 0..n {
+                    tree.iterate(&mut self.rollout, self.exploration, &mut self.rng);
+                }
+            }
+            Budget::Time(duration) => {
+                let start = Instant::now();
+                while start.elapsed() < duration {
+                    tree.iterate(&mut self.rollout, self.exploration, &mut self.rng);
+                }
+            }
+        }
+
+        // The most visited move is the most robust choice.
+        let (moves, children) = match &tree.nodes[0].kind {
+            Kind::Decision { moves, children, .. } => (moves, children),
+            _ => unreachable!(),
+        };
+        let mut best = moves[0];
+        let mut best_visits = 0;
+        for (&place, &child) in moves.iter().zip(children) {
+            if tree.nodes[child].visits > best_visits {
+                best_visits = tree.nodes[child].visits;
+                best = place;
+            }
+        }
+
+        best
+    }
+}
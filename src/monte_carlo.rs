@@ -0,0 +1,136 @@
+use std::thread;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::game_manager::*;
+use crate::tournament::*;
+use crate::players::*;
+
+// The policy used by both players to finish a game in a rollout.
+#[derive(Clone, Copy)]
+pub enum RolloutPolicy {
+    Random,
+    Greedy,
+}
+
+// Pure Monte Carlo player: each legal move is played, then the game is finished
+// `nb_rollouts` times with the rollout policy. The move with the best win rate
+// is chosen. There is no tree: this is a baseline to measure what search
+// brings.
+//
+// The rollouts are split between `nb_threads` threads. Each thread gets its
+// own random generator, seeded from `rng`, so that the results only depend on
+// the seed of `rng` and not on the scheduling of the threads.
+//
+// The player is built with `seeded`, which checks that `nb_rollouts` is not 0.
+pub struct MonteCarloPlayer {
+    pub policy: RolloutPolicy,
+    nb_rollouts: u32,
+    pub nb_threads: usize,
+    pub rng: StdRng,
+}
+
+impl MonteCarloPlayer {
+    // Returns a player whose random generator is seeded with `seed`.
+    // Panics if `nb_rollouts` is 0, as no move could then be rated.
+    pub fn seeded(policy: RolloutPolicy, nb_rollouts: u32, nb_threads: usize,
+                  seed: u64) -> MonteCarloPlayer {
+        assert!(nb_rollouts > 0, "A Monte Carlo player needs at least one rollout");
+        MonteCarloPlayer {
+            policy,
+            nb_rollouts,
+            nb_threads,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Returns the win rate of each move of `moves` for the player to play.
+    pub fn win_rates(&mut self, board: &Board, dice: usize, moves: &[usize])
+        -> Vec<f32> {
+        let nb_threads = self.nb_threads.max(1);
+        let seeds: Vec<u64> = (0..nb_threads).map(|_| self.rng.gen()).collect();
+        let policy = self.policy;
+        let nb_rollouts = self.nb_rollouts;
+
+        let wins = thread::scope(|s| {
+            let handles: Vec<_> = seeds.iter().enumerate().map(|(t, &seed)| {
+                // The first threads take the remainder of the division.
+                let mut n = nb_rollouts / nb_threads as u32;
+                if (t as u32) < nb_rollouts % nb_threads as u32 {
+                    n += 1;
+                }
+                s.spawn(move || count_wins(board, dice, moves, policy, n, seed))
+            }).collect();
+
+            let mut wins = vec![0; moves.len()];
+            for handle in handles {
+                for (w, x) in wins.iter_mut().zip(handle.join().unwrap()) {
+                    *w += x;
+                }
+            }
+            wins
+        });
+
+        wins.iter().map(|&w| w as f32 / nb_rollouts as f32).collect()
+    }
+}
+
+// For each move, plays it then finishes the game `n` times. Returns the number
+// of games won by the player to play for each move.
+fn count_wins(
+    board: &Board,
+    dice: usize,
+    moves: &[usize],
+    policy: RolloutPolicy,
+    n: u32,
+    seed: u64
+) -> Vec<u32> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    match policy {
+        RolloutPolicy::Random => {
+            let mut p = RandMove {rng: StdRng::seed_from_u64(rng.gen())};
+            count_wins_with(board, dice, moves, &mut p, n, &mut rng)
+        }
+        RolloutPolicy::Greedy => {
+            count_wins_with(board, dice, moves, &mut Greedy {}, n, &mut rng)
+        }
+    }
+}
+
+fn count_wins_with<P: Player>(
+    board: &Board,
+    dice: usize,
+    moves: &[usize],
+    policy: &mut P,
+    n: u32,
+    rng: &mut StdRng
+) -> Vec<u32> {
+    let player = board.turn;
+    moves.iter().map(|&place| {
+        let mut copy = board.clone();
+        if copy.perform_move(dice, place) {
+            return n;
+        }
+        // A rollout policy that plays an illegal move loses the game.
+        (0..n).filter(|_| {
+            play_out(&copy, policy, rng).unwrap_or_else(|f| 1 - f.player) == player
+        }).count() as u32
+    }).collect()
+}
+
+impl Player for MonteCarloPlayer {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        let moves = board.possible_moves(dice);
+        let rates = self.win_rates(board, dice, &moves);
+        let mut best = moves[0];
+        let mut best_rate = rates[0];
+        for (&place, &rate) in moves.iter().zip(&rates).skip(1) {
+            if rate > best_rate {
+                best_rate = rate;
+                best = place;
+            }
+        }
+
+        best
+    }
+}
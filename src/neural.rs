@@ -0,0 +1,262 @@
+use rand::Rng;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::reinforcement_learning::*;
+use crate::features::*;
+use crate::weights::*;
+
+// The number of inputs of the network given by `inputs`.
+pub const NB_INPUTS: usize = 32;
+
+// The inputs given to the network.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    // Only the occupancy of the board, see `inputs`.
+    Board,
+    // The occupancy of the board followed by `Features::to_vec`.
+    Rich,
+}
+
+impl Encoding {
+    pub fn size(&self) -> usize {
+        match self {
+            Encoding::Board => NB_INPUTS,
+            Encoding::Rich => NB_INPUTS + NB_FEATURES,
+        }
+    }
+
+    pub fn encode(&self, board: &Board) -> Vec<f32> {
+        let mut x = inputs(board);
+        if *self == Encoding::Rich {
+            x.extend(Features::new(board).to_vec());
+        }
+
+        x
+    }
+}
+
+// The activation of the output neuron.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    // The output is the value, between -1 and 1.
+    Tanh,
+    // The output is the probability p that the player to play wins, and the
+    // value is 2p - 1.
+    Sigmoid,
+}
+
+// A multilayer perceptron. The hidden layers use tanh activations.
+//
+// The network takes the board from the point of view of the player that is
+// to play (see `inputs`), so that it can learn interactions between the pieces
+// of both players, which `LinearEval0` cannot.
+#[derive(Clone)]
+pub struct Mlp {
+    // The number of neurons of each layer, from the inputs to the output.
+    // The last layer has a single neuron.
+    sizes: Vec<usize>,
+    // weights[l] is the matrix from the layer l to the layer l + 1, stored
+    // row by row. Each row has sizes[l] + 1 elements: the last one is the bias.
+    weights: Vec<Vec<f32>>,
+    output: Output,
+    encoding: Encoding,
+}
+
+// The inputs of the network for a board, seen from the player that is to
+// play: the cells of this player, the cells of the adversary, then the ready
+// and out pieces of both players divided by 7.
+pub fn inputs(board: &Board) -> Vec<f32> {
+    let player = board.turn;
+    let adv = 1 - player;
+    let mut x = Vec::with_capacity(NB_INPUTS);
+    for &p in &[player, adv] {
+        for i in 0..14 {
+            x.push(if board.cells[p][i] { 1.0 } else { 0.0 });
+        }
+    }
+    for &p in &[player, adv] {
+        x.push(board.ready[p] as f32 / 7.0);
+        x.push(board.out[p] as f32 / 7.0);
+    }
+
+    x
+}
+
+impl Mlp {
+    // Returns a network with the given hidden layers, whose weights are drawn
+    // uniformly in [-1/sqrt(n), 1/sqrt(n)], where n is the number of inputs of
+    // each neuron.
+    pub fn new<R: Rng>(
+        hidden: &[usize],
+        output: Output,
+        encoding: Encoding,
+        rng: &mut R
+    ) -> Mlp {
+        let mut sizes = vec![encoding.size()];
+        sizes.extend_from_slice(hidden);
+        sizes.push(1);
+        let weights = sizes.windows(2).map(|w| {
+            let bound = 1.0 / (w[0] as f32).sqrt();
+            (0..w[1] * (w[0] + 1)).map(|_| rng.gen_range(-bound..bound)).collect()
+        }).collect();
+
+        Mlp { sizes, weights, output, encoding }
+    }
+
+    // Returns the activations of every layer, from the inputs to the output.
+    // The output is given before the output activation.
+    fn forward(&self, board: &Board) -> Vec<Vec<f32>> {
+        let mut layers = vec![self.encoding.encode(board)];
+        for (l, w) in self.weights.iter().enumerate() {
+            let x = &layers[l];
+            let n = self.sizes[l];
+            let last = l + 1 == self.weights.len();
+            let y = w.chunks(n + 1).map(|row| {
+                let mut s = row[n];
+                for (wi, xi) in row.iter().zip(x) {
+                    s += wi * xi;
+                }
+                if last { s } else { s.tanh() }
+            }).collect();
+            layers.push(y);
+        }
+
+        layers
+    }
+
+    // Returns the output of the network.
+    fn out(&self, z: f32) -> f32 {
+        match self.output {
+            Output::Tanh => z.tanh(),
+            Output::Sigmoid => 1.0 / (1.0 + (-z).exp()),
+        }
+    }
+}
+
+// The model of a network is "mlp <output> <encoding> <sizes of the layers>",
+// for instance "mlp sigmoid board 32 40 1".
+impl Persistent for Mlp {
+    fn model(&self) -> String {
+        let output = match self.output {
+            Output::Tanh => "tanh",
+            Output::Sigmoid => "sigmoid",
+        };
+        let encoding = match self.encoding {
+            Encoding::Board => "board",
+            Encoding::Rich => "rich",
+        };
+        let sizes: Vec<String> = self.sizes.iter().map(|s| s.to_string()).collect();
+        format!("mlp {} {} {}", output, encoding, sizes.join(" "))
+    }
+
+    fn from_model(model: &str) -> Option<Self> {
+        let mut words = model.split_whitespace();
+        if words.next() != Some("mlp") {
+            return None;
+        }
+        let output = match words.next()? {
+            "tanh" => Output::Tanh,
+            "sigmoid" => Output::Sigmoid,
+            _ => return None,
+        };
+        let encoding = match words.next()? {
+            "board" => Encoding::Board,
+            "rich" => Encoding::Rich,
+            _ => return None,
+        };
+        let sizes = words.map(|w| w.parse::<usize>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if sizes.len() < 2 || sizes[0] != encoding.size() ||
+           sizes[sizes.len() - 1] != 1 {
+            return None;
+        }
+        let weights = sizes.windows(2).map(|w| vec![0.0; w[1] * (w[0] + 1)])
+            .collect();
+
+        Some(Mlp { sizes, weights, output, encoding })
+    }
+}
+
+impl Heuristic for Mlp {
+    fn victory() -> f32 {
+        1.0
+    }
+
+    fn eval(&self, board: &Board) -> f32 {
+        let layers = self.forward(board);
+        let y = self.out(layers[layers.len() - 1][0]);
+        match self.output {
+            Output::Tanh => y,
+            Output::Sigmoid => 2.0 * y - 1.0,
+        }
+    }
+}
+
+impl WinProbability for Mlp {
+    fn win_probability(&self, board: &Board) -> f32 {
+        (self.eval(board) + 1.0) / 2.0
+    }
+}
+
+impl TrainableHeuristic for Mlp {
+    fn features(&self, board: &Board) -> Vec<f32> {
+        self.encoding.encode(board)
+    }
+
+    // Backpropagation of the value through the layers.
+    fn gradient(&self, board: &Board) -> Vec<f32> {
+        let layers = self.forward(board);
+        let nb_layers = self.weights.len();
+        let y = self.out(layers[nb_layers][0]);
+        // The derivative of the value with respect to the output before the
+        // activation.
+        let mut delta = vec![match self.output {
+            Output::Tanh => 1.0 - y * y,
+            Output::Sigmoid => 2.0 * y * (1.0 - y),
+        }];
+
+        let mut grads = vec![Vec::new(); nb_layers];
+        for l in (0..nb_layers).rev() {
+            let n = self.sizes[l];
+            let x = &layers[l];
+            let w = &self.weights[l];
+            let mut grad = Vec::with_capacity(w.len());
+            for &d in &delta {
+                grad.extend(x.iter().map(|xi| d * xi));
+                grad.push(d);
+            }
+            grads[l] = grad;
+
+            if l > 0 {
+                // The hidden layer l uses tanh: its derivative is 1 - x².
+                delta = (0..n).map(|i| {
+                    let s: f32 = delta.iter().enumerate()
+                        .map(|(j, d)| d * w[j * (n + 1) + i])
+                        .sum();
+                    s * (1.0 - x[i] * x[i])
+                }).collect();
+            }
+        }
+
+        grads.concat()
+    }
+
+    fn params(&self) -> Vec<f32> {
+        self.weights.concat()
+    }
+
+    fn set_params(&mut self, params: &[f32]) {
+        let mut start = 0;
+        for w in self.weights.iter_mut() {
+            let end = start + w.len();
+            w.copy_from_slice(&params[start..end]);
+            start = end;
+        }
+    }
+
+    fn disp(&self) {
+        let sizes: Vec<String> = self.sizes.iter().map(|s| s.to_string()).collect();
+        println!("MLP {} ({} weights)", sizes.join("-"), self.params().len());
+    }
+}
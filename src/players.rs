@@ -0,0 +1,69 @@
+use std::io;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::tournament::*;
+use crate::weights::*;
+
+// Always plays the most advanced piece that can be moved.
+pub struct LastMove {}
+impl Player for LastMove {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        *board.possible_moves(dice).last().unwrap()
+    }
+}
+
+// Plays a legal move chosen uniformly at random.
+pub struct RandMove<R: Rng> {
+    pub rng: R,
+}
+impl<R: Rng> Player for RandMove<R> {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        *board.possible_moves(dice).choose(&mut self.rng).unwrap()
+    }
+}
+
+// Takes an opponent's piece if possible, otherwise lands on a rosetta if
+// possible, otherwise plays the most advanced piece.
+pub struct Greedy {}
+impl Player for Greedy {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        let moves = board.possible_moves(dice);
+        let adv = 1 - board.turn;
+        for &place in moves.iter().rev() {
+            if is_central(place + dice) && board.cells[adv][place + dice] {
+                return place;
+            }
+        }
+        for &place in moves.iter().rev() {
+            if is_rosetta(place + dice) || (place == ENTER && dice == 4) {
+                return place;
+            }
+        }
+        *moves.last().unwrap()
+    }
+}
+
+// Plays the move with the best expectimax evaluation. `depth` counts the
+// move being chosen, so it should be at least 1.
+pub struct ExpectimaxPlayer<H: Heuristic> {
+    pub h: H,
+    pub depth: u32,
+    pub options: SearchOptions,
+}
+
+impl<H: Persistent> ExpectimaxPlayer<H> {
+    // Returns a player using the evaluator of a weight file.
+    pub fn load(path: &str, depth: u32) -> io::Result<ExpectimaxPlayer<H>> {
+        let (h, _) = load(path)?;
+        Ok(ExpectimaxPlayer { h, depth, options: SearchOptions::default() })
+    }
+}
+
+impl<H: Heuristic> Player for ExpectimaxPlayer<H> {
+    fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+        best_move_with(&self.h, board, dice, self.depth - 1, &self.options).0
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::io;
+use rand::Rng;
+
+use crate::game_manager::*;
+use crate::tournament::*;
+
+// A game record: the starting position and the dice rolls with the moves that
+// were played. Records are text files:
+//
+// start <notation of the starting position, see `Board::notation`>
+// <dice> <move>
+// ...
+//
+// with one line per dice roll. The move is "enter", the index of the piece
+// that is moved, or "-" when no move is possible. A game lost by a player that
+// chose an illegal move ends with the line:
+//
+// forfeit <dice> <move>
+pub struct Record {
+    pub start: Board,
+    pub rolls: Vec<Roll>,
+    pub forfeit: Option<Forfeit>,
+}
+
+pub struct Roll {
+    pub dice: usize,
+    // None when no move is possible.
+    pub place: Option<usize>,
+}
+
+// An illegal move chosen by a player, who loses the game.
+pub struct Forfeit {
+    pub player: usize,
+    pub dice: usize,
+    pub place: usize,
+    pub error: IllegalMove,
+}
+
+// The notation of a move in the records.
+pub fn move_notation(place: Option<usize>) -> String {
+    match place {
+        None => "-".to_string(),
+        Some(ENTER) => "enter".to_string(),
+        Some(place) => place.to_string(),
+    }
+}
+
+// A description of a move for the players, e.g. "4 -> 6".
+pub fn move_description(dice: usize, place: usize) -> String {
+    if place == ENTER {
+        format!("enter -> {}", dice - 1)
+    }
+    else if place + dice == 14 {
+        format!("{} -> out", place)
+    }
+    else {
+        format!("{} -> {}", place, place + dice)
+    }
+}
+
+// Reads the notation of a move. Returns None if it is invalid.
+pub fn parse_move(s: &str) -> Option<Option<usize>> {
+    match s {
+        "-" => Some(None),
+        "enter" => Some(Some(ENTER)),
+        s => s.parse().ok().filter(|&place| place < 14).map(Some),
+    }
+}
+
+// Reads the dice and the move of a forfeit in `board`. Fails if the move is
+// legal.
+fn parse_forfeit(board: &Board, fields: &[&str]) -> Result<Forfeit, String> {
+    if fields.len() != 2 {
+        return Err("expected a dice and a move".to_string());
+    }
+    let dice = fields[0].parse().map_err(|_| "invalid dice")?;
+    let place = match fields[1] {
+        "enter" => ENTER,
+        s => s.parse().map_err(|_| "invalid move")?,
+    };
+    match board.check_move(dice, place) {
+        Ok(()) => Err("a legal move cannot be forfeited".to_string()),
+        Err(error) => Ok(Forfeit { player: board.turn, dice, place, error }),
+    }
+}
+
+impl Record {
+    pub fn new(start: Board) -> Record {
+        Record { start, rolls: Vec::new(), forfeit: None }
+    }
+
+    // Plays a game from `start` between two players, player_a playing first.
+    // A player that chooses an illegal move forfeits the game.
+    pub fn play<R: Rng>(
+        start: Board,
+        player_a: &mut impl Player,
+        player_b: &mut impl Player,
+        rng: &mut R
+    ) -> Record {
+        let first = start.turn;
+        let mut record = Record::new(start);
+        let mut board = record.start.clone();
+        loop {
+            let dice = roll_dices(rng);
+            let places = board.possible_moves(dice);
+            if places.is_empty() {
+                record.rolls.push(Roll { dice, place: None });
+                board.change_turn();
+                continue;
+            }
+            let place = if places.len() == 1 {
+                // No need to call a choice function if there is only one
+                // possible move.
+                places[0]
+            }
+            else if board.turn == first {
+                player_a.choose_move(&board, dice)
+            }
+            else {
+                player_b.choose_move(&board, dice)
+            };
+            match board.try_perform_move(dice, place) {
+                Ok(outcome) => {
+                    record.rolls.push(Roll { dice, place: Some(place) });
+                    if outcome == MoveOutcome::Won {
+                        return record;
+                    }
+                }
+                Err(error) => {
+                    let player = board.turn;
+                    record.forfeit = Some(Forfeit { player, dice, place, error });
+                    return record;
+                }
+            }
+        }
+    }
+
+    // The positions before each dice roll, followed by the final position.
+    pub fn positions(&self) -> Vec<Board> {
+        let mut board = self.start.clone();
+        let mut positions = vec![board.clone()];
+        for roll in &self.rolls {
+            match roll.place {
+                Some(place) => {
+                    board.perform_move(roll.dice, place);
+                }
+                None => board.change_turn(),
+            }
+            positions.push(board.clone());
+        }
+
+        positions
+    }
+
+    // The winner, or None if the game is not finished.
+    pub fn winner(&self) -> Option<usize> {
+        if let Some(forfeit) = &self.forfeit {
+            return Some(1 - forfeit.player);
+        }
+        let board = self.positions().pop().unwrap();
+        // The turn does not change after a winning move.
+        if board.out[board.turn] == 7 { Some(board.turn) } else { None }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("start {}\n", self.start.notation());
+        for roll in &self.rolls {
+            text += &format!("{} {}\n", roll.dice, move_notation(roll.place));
+        }
+        if let Some(forfeit) = &self.forfeit {
+            text += &format!("forfeit {} {}\n", forfeit.dice,
+                             move_notation(Some(forfeit.place)));
+        }
+
+        text
+    }
+
+    // Reads a record written by `to_text`. Fails if a move is not legal.
+    pub fn parse(text: &str) -> Result<Record, String> {
+        let mut lines = text.lines().enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let start = lines.next()
+            .and_then(|(_, l)| l.strip_prefix("start "))
+            .ok_or("missing starting position")?;
+        let mut board = Board::from_notation(start)
+            .map_err(|e| format!("starting position: {}", e))?;
+        let mut record = Record::new(board.clone());
+        let mut finished = false;
+
+        for (i, line) in lines {
+            let error = |msg: &str| format!("line {}: {}", i + 1, msg);
+            if finished {
+                return Err(error("the game is already finished"));
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.first() == Some(&"forfeit") {
+                record.forfeit = Some(parse_forfeit(&board, &fields[1..])
+                    .map_err(|msg| error(&msg))?);
+                finished = true;
+                continue;
+            }
+            if fields.len() != 2 {
+                return Err(error("expected a dice and a move"));
+            }
+            let dice = fields[0].parse().ok().filter(|&dice| dice <= 4)
+                .ok_or_else(|| error("invalid dice"))?;
+            let place = parse_move(fields[1]).ok_or_else(|| error("invalid move"))?;
+            let places = board.possible_moves(dice);
+            match place {
+                None if places.is_empty() => board.change_turn(),
+                Some(place) if places.contains(&place) => {
+                    finished = board.perform_move(dice, place);
+                }
+                _ => return Err(error("illegal move")),
+            }
+            record.rolls.push(Roll { dice, place });
+        }
+
+        Ok(record)
+    }
+
+    pub fn load(path: &str) -> io::Result<Record> {
+        let text = fs::read_to_string(path)?;
+        Record::parse(&text).map_err(|msg| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+        })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+}
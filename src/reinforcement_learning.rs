@@ -1,7 +1,49 @@
+use rand::Rng;
+
 use crate::game_manager::*;
 
 use crate::strategy::*;
 
+use crate::features::*;
+
+// A heuristic whose evaluation is a differentiable function of a vector of
+// parameters, so that it can be trained by gradient descent.
+pub trait TrainableHeuristic: Heuristic {
+    // The inputs of the evaluator for a board, seen from the player that is to
+    // play.
+    fn features(&self, board: &Board) -> Vec<f32>;
+    // The gradient of `self.eval(board)` with respect to the parameters.
+    fn gradient(&self, board: &Board) -> Vec<f32>;
+    fn params(&self) -> Vec<f32>;
+    // `params` must have the same length as `self.params()`.
+    fn set_params(&mut self, params: &[f32]);
+
+    // Display the current values of the parameters.
+    fn disp(&self) {
+        for (i, p) in self.params().iter().enumerate() {
+            println!("{:4}: {}", i, p);
+        }
+    }
+
+    // Adds k * v to the parameters.
+    fn add_scaled(&mut self, v: &[f32], k: f32) {
+        let mut params = self.params();
+        for (p, x) in params.iter_mut().zip(v) {
+            *p += k * x;
+        }
+        self.set_params(&params);
+    }
+
+    // Given a board, do a gradient descent step to shift the evaluation of the
+    // board closer to a target value.
+    // alpha is the (supposedly low) learning coefficient.
+    fn step(&mut self, board: &Board, target: f32, alpha: f32) {
+        let diff = alpha * (target - self.eval(board));
+        let grad = self.gradient(board);
+        self.add_scaled(&grad, diff);
+    }
+}
+
 // The approximation that is going to be trained.
 // To evaluate a board, the value for the player that is going to play is:
 // val_ready * (board.ready[player] - board.ready[adv]) +
@@ -14,15 +56,14 @@ use crate::strategy::*;
 //       adv    = 1 - board.turn
 //
 // The value for the other player is the opposite.
-#[derive(Default)]
+// See `TanhEval0` for a version whose values are between -1 and 1.
+#[derive(Clone, Default)]
 pub struct LinearEval0 {
     pub val_ready: f32,
     pub val_cells: [f32; 14],
     pub val_out: f32,
     pub player_adv: f32,
 }
-// TODO: take the hyperbolic tangent of the sum, so that the value is between
-// -1 and 1.
 
 impl Heuristic for LinearEval0 {
     fn victory() -> f32 {
@@ -50,9 +91,52 @@ impl Heuristic for LinearEval0 {
     }
 }
 
-impl LinearEval0 {
-    // Display the current values of the parameters.
-    pub fn disp(&self) {
+// The parameters are, in this order: val_ready, val_cells, val_out and
+// player_adv.
+impl TrainableHeuristic for LinearEval0 {
+    fn features(&self, board: &Board) -> Vec<f32> {
+        let player = board.turn;
+        let adv = 1 - player;
+        let mut features = Vec::with_capacity(17);
+        features.push(board.ready[player] as f32 - board.ready[adv] as f32);
+        for i in 0..14 {
+            let mut x = 0.0;
+            if board.cells[player][i] {
+                x += 1.0;
+            }
+            if board.cells[adv][i] {
+                x -= 1.0;
+            }
+            features.push(x);
+        }
+        features.push(board.out[player] as f32 - board.out[adv] as f32);
+        features.push(1.0);
+
+        features
+    }
+
+    // Since the evaluation is linear, the gradient is the vector of features.
+    fn gradient(&self, board: &Board) -> Vec<f32> {
+        self.features(board)
+    }
+
+    fn params(&self) -> Vec<f32> {
+        let mut params = vec![self.val_ready];
+        params.extend_from_slice(&self.val_cells);
+        params.push(self.val_out);
+        params.push(self.player_adv);
+
+        params
+    }
+
+    fn set_params(&mut self, params: &[f32]) {
+        self.val_ready = params[0];
+        self.val_cells.copy_from_slice(&params[1..15]);
+        self.val_out = params[15];
+        self.player_adv = params[16];
+    }
+
+    fn disp(&self) {
         println!("READY: {}", self.val_ready);
         for i in 0..4 {
             println!("A{0}-C{0}: {1}", 4 - i, self.val_cells[i]);
@@ -66,27 +150,439 @@ impl LinearEval0 {
         println!("OUT  : {}", self.val_out);
         println!("ADV  : {}", self.player_adv);
     }
+}
 
-    // Given a board, do a gradient descent step to shift the evaluation of the
-    // board closer to a target value.
-    // alpha is the (supposedly low) learning coefficient.
-    pub fn step(&mut self, board: &Board, target: f32, alpha: f32) {
-        let diff = alpha * (target - self.eval(board));
+// The hyperbolic tangent of `LinearEval0`, so that the values are between -1
+// and 1, like the victory value.
+#[derive(Clone, Default)]
+pub struct TanhEval0 {
+    pub linear: LinearEval0,
+}
 
-        let player = board.turn;
-        let adv = 1 - player;
-        self.val_ready += board.ready[player] as f32 * diff;
-        self.val_ready -= board.ready[adv] as f32 * diff;
-        for i in 0..14 {
-            if board.cells[player][i] {
-                self.val_cells[i] += diff;
+impl Heuristic for TanhEval0 {
+    fn victory() -> f32 {
+        1.0
+    }
+
+    fn eval(&self, board: &Board) -> f32 {
+        self.linear.eval(board).tanh()
+    }
+}
+
+impl TrainableHeuristic for TanhEval0 {
+    fn features(&self, board: &Board) -> Vec<f32> {
+        self.linear.features(board)
+    }
+
+    // d tanh(x) = (1 - tanh(x)²) dx
+    fn gradient(&self, board: &Board) -> Vec<f32> {
+        let val = self.eval(board);
+        let k = 1.0 - val * val;
+        self.linear.gradient(board).iter().map(|x| k * x).collect()
+    }
+
+    fn params(&self) -> Vec<f32> {
+        self.linear.params()
+    }
+
+    fn set_params(&mut self, params: &[f32]) {
+        self.linear.set_params(params);
+    }
+
+    fn disp(&self) {
+        self.linear.disp();
+    }
+}
+
+// A linear evaluation over both the features of `LinearEval0` and the
+// features of the `features` module, such as the danger of each piece.
+#[derive(Clone)]
+pub struct FeatureEval {
+    pub weights: Vec<f32>,
+}
+
+impl Default for FeatureEval {
+    fn default() -> Self {
+        FeatureEval { weights: vec![0.0; 17 + NB_FEATURES] }
+    }
+}
+
+impl Heuristic for FeatureEval {
+    fn victory() -> f32 {
+        1.0
+    }
+
+    fn eval(&self, board: &Board) -> f32 {
+        self.features(board).iter().zip(&self.weights).map(|(x, w)| x * w).sum()
+    }
+}
+
+impl TrainableHeuristic for FeatureEval {
+    fn features(&self, board: &Board) -> Vec<f32> {
+        let mut features = LinearEval0::default().features(board);
+        features.extend(Features::new(board).to_vec());
+
+        features
+    }
+
+    fn gradient(&self, board: &Board) -> Vec<f32> {
+        self.features(board)
+    }
+
+    fn params(&self) -> Vec<f32> {
+        self.weights.clone()
+    }
+
+    fn set_params(&mut self, params: &[f32]) {
+        self.weights.copy_from_slice(params);
+    }
+}
+
+// A coefficient, such as the learning coefficient or the exploration rate,
+// as a function of the number of games played.
+#[derive(Clone, Copy)]
+pub enum Schedule {
+    Constant(f32),
+    // initial / (1 + decay * games)
+    InverseTime { initial: f32, decay: f32 },
+    // initial * rate^games
+    Exponential { initial: f32, rate: f32 },
+}
+
+impl Schedule {
+    pub fn value(&self, games: u32) -> f32 {
+        match *self {
+            Schedule::Constant(value) => value,
+            Schedule::InverseTime { initial, decay } =>
+                initial / (1.0 + decay * games as f32),
+            Schedule::Exponential { initial, rate } =>
+                initial * rate.powi(games as i32),
+        }
+    }
+}
+
+// How the moves are chosen during self-play.
+#[derive(Clone, Copy)]
+pub enum Exploration {
+    // Always the best move.
+    Greedy,
+    // A random move with probability epsilon, the best move otherwise.
+    EpsilonGreedy(Schedule),
+    // A move chosen with probability proportional to exp(value / temperature).
+    Softmax(Schedule),
+}
+
+// Chooses the moves of self-play games, and counts how often a move that is
+// not the best one is chosen.
+pub struct Explorer {
+    pub exploration: Exploration,
+    // The number of moves chosen among several possible moves.
+    pub moves: u64,
+    // The number of those moves whose value is lower than the best value.
+    pub exploratory: u64,
+}
+
+impl Explorer {
+    pub fn new(exploration: Exploration) -> Explorer {
+        Explorer { exploration, moves: 0, exploratory: 0 }
+    }
+
+    // The rate of exploratory moves since the last reset.
+    pub fn rate(&self) -> f32 {
+        if self.moves == 0 { 0.0 } else { self.exploratory as f32 / self.moves as f32 }
+    }
+
+    pub fn reset(&mut self) {
+        self.moves = 0;
+        self.exploratory = 0;
+    }
+
+    // Chooses a move given a dice roll, with the values of the moves given by
+    // `h` at depth 0. `games` is the number of games played, for the
+    // schedules. There must be at least one possible move.
+    pub fn choose_move<H, R>(
+        &mut self,
+        h: &H,
+        board: &Board,
+        dice: usize,
+        games: u32,
+        rng: &mut R
+    ) -> usize
+    where H: Heuristic, R: Rng
+    {
+        let moves = board.possible_moves(dice);
+        if moves.len() == 1 {
+            return moves[0];
+        }
+        let values: Vec<f32> = moves.iter()
+            .map(|&place| eval_move(h, board, dice, place, 0))
+            .collect();
+        let best = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let greedy = values.iter().position(|&v| v == best).unwrap();
+
+        let chosen = match self.exploration {
+            Exploration::Greedy => greedy,
+            Exploration::EpsilonGreedy(epsilon) => {
+                if rng.gen::<f32>() < epsilon.value(games) {
+                    rng.gen_range(0..moves.len())
+                }
+                else {
+                    greedy
+                }
             }
-            if board.cells[adv][i] {
-                self.val_cells[i] -= diff;
+            Exploration::Softmax(temperature) => {
+                let t = temperature.value(games);
+                // Subtracting the best value avoids overflows.
+                let weights: Vec<f32> =
+                    values.iter().map(|v| ((v - best) / t).exp()).collect();
+                let mut x = rng.gen::<f32>() * weights.iter().sum::<f32>();
+                let mut chosen = moves.len() - 1;
+                for (i, w) in weights.iter().enumerate() {
+                    if x < *w {
+                        chosen = i;
+                        break;
+                    }
+                    x -= w;
+                }
+                chosen
+            }
+        };
+
+        self.moves += 1;
+        if values[chosen] < best {
+            self.exploratory += 1;
+        }
+
+        moves[chosen]
+    }
+
+    // Plays one game where both players choose their moves with the explorer,
+    // and records the positions before each dice roll.
+    pub fn play_game<H, R>(&mut self, h: &H, games: u32, rng: &mut R) -> Game
+    where H: Heuristic, R: Rng
+    {
+        let mut positions = Vec::new();
+        let mut exploratory = Vec::new();
+        let mut board = Board::default();
+        let winner = loop {
+            positions.push(board.clone());
+            let dice = roll_dices(rng);
+            if board.possible_moves(dice).is_empty() {
+                exploratory.push(false);
+                board.change_turn();
+                continue;
+            }
+            let before = self.exploratory;
+            let place = self.choose_move(h, &board, dice, games, rng);
+            exploratory.push(self.exploratory > before);
+            if board.perform_move(dice, place) {
+                break board.turn;
+            }
+        };
+
+        Game { positions, exploratory, winner }
+    }
+}
+
+// A recorded self-play game.
+pub struct Game {
+    // The positions before each dice roll.
+    pub positions: Vec<Board>,
+    // Whether the move played in each position was exploratory.
+    pub exploratory: Vec<bool>,
+    pub winner: usize,
+}
+
+// TD(λ) training by self-play, with eligibility traces over whole games.
+//
+// The values are taken from the point of view of player 0, so that the
+// successive positions of a game can be compared. The target of the last
+// position is the outcome of the game: the victory value of the heuristic for
+// a win of player 0, its opposite for a loss.
+//
+// The traces are reset at an exploratory move, and the evaluator is not
+// updated for it: the positions before it are not updated toward the outcome
+// of a move the greedy policy would not have played.
+pub struct TdLambda {
+    pub lambda: f32,
+    pub schedule: Schedule,
+    pub explorer: Explorer,
+    // The number of games played so far.
+    pub games: u32,
+}
+
+impl TdLambda {
+    // Plays one game where both players choose their moves with the explorer,
+    // and updates the evaluator after each move.
+    // Returns the game played.
+    pub fn train_game<T, R>(&mut self, evaluator: &mut T, rng: &mut R) -> Game
+    where T: TrainableHeuristic, R: Rng
+    {
+        let alpha = self.schedule.value(self.games);
+        let sign = |board: &Board| if board.turn == 0 { 1.0 } else { -1.0 };
+        let mut traces = vec![0.0; evaluator.params().len()];
+        let mut board = Board::default();
+        let mut positions = Vec::new();
+        let mut exploratory = Vec::new();
+        let winner = loop {
+            positions.push(board.clone());
+            let val = sign(&board) * evaluator.eval(&board);
+            let grad = evaluator.gradient(&board);
+            for (e, g) in traces.iter_mut().zip(grad) {
+                *e = self.lambda * *e + sign(&board) * g;
+            }
+
+            let dice = roll_dices(rng);
+            let before = self.explorer.exploratory;
+            let mut won = false;
+            if board.possible_moves(dice).is_empty() {
+                board.change_turn();
+            }
+            else {
+                let place =
+                    self.explorer.choose_move(evaluator, &board, dice, self.games, rng);
+                won = board.perform_move(dice, place);
             }
+            let explored = self.explorer.exploratory > before;
+            exploratory.push(explored);
+
+            if explored {
+                traces.iter_mut().for_each(|e| *e = 0.0);
+            }
+            else {
+                let target = if won { T::victory() } else { evaluator.eval(&board) };
+                evaluator.add_scaled(&traces, alpha * (sign(&board) * target - val));
+            }
+            if won {
+                break board.turn;
+            }
+        };
+        self.games += 1;
+
+        Game { positions, exploratory, winner }
+    }
+
+    // Updates the evaluator on a recorded game, with the same targets as
+    // `train_game`. The game is not counted in `games`.
+    pub fn train_on<T>(&self, evaluator: &mut T, game: &Game)
+    where T: TrainableHeuristic
+    {
+        let alpha = self.schedule.value(self.games);
+        let sign = |board: &Board| if board.turn == 0 { 1.0 } else { -1.0 };
+        let mut traces = vec![0.0; evaluator.params().len()];
+        for (i, board) in game.positions.iter().enumerate() {
+            let val = sign(board) * evaluator.eval(board);
+            let grad = evaluator.gradient(board);
+            for (e, g) in traces.iter_mut().zip(grad) {
+                *e = self.lambda * *e + sign(board) * g;
+            }
+            if game.exploratory[i] {
+                traces.iter_mut().for_each(|e| *e = 0.0);
+                continue;
+            }
+            let target = match game.positions.get(i + 1) {
+                Some(next) => sign(next) * evaluator.eval(next),
+                None if game.winner == 0 => T::victory(),
+                None => -T::victory(),
+            };
+            evaluator.add_scaled(&traces, alpha * (target - val));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // The linear evaluation, counting its gradients (one per position of a
+    // game) and its updates.
+    #[derive(Default)]
+    struct Counted {
+        h: LinearEval0,
+        gradients: Cell<u64>,
+        updates: u64,
+    }
+
+    impl Heuristic for Counted {
+        fn victory() -> f32 {
+            LinearEval0::victory()
         }
-        self.val_out += board.out[player] as f32 * diff;
-        self.val_out -= board.out[adv] as f32 * diff;
-        self.player_adv += diff;
+
+        fn eval(&self, board: &Board) -> f32 {
+            self.h.eval(board)
+        }
+    }
+
+    impl TrainableHeuristic for Counted {
+        fn features(&self, board: &Board) -> Vec<f32> {
+            self.h.features(board)
+        }
+
+        fn gradient(&self, board: &Board) -> Vec<f32> {
+            self.gradients.set(self.gradients.get() + 1);
+            self.h.gradient(board)
+        }
+
+        fn params(&self) -> Vec<f32> {
+            self.h.params()
+        }
+
+        fn set_params(&mut self, params: &[f32]) {
+            self.h.set_params(params)
+        }
+
+        fn add_scaled(&mut self, v: &[f32], k: f32) {
+            self.updates += 1;
+            self.h.add_scaled(v, k)
+        }
+    }
+
+    #[test]
+    fn exploratory_moves_are_not_learned() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let exploration = Exploration::EpsilonGreedy(Schedule::Constant(0.5));
+        let mut td = TdLambda {
+            lambda: 0.7,
+            schedule: Schedule::Constant(1e-2),
+            explorer: Explorer::new(exploration),
+            games: 0,
+        };
+        // Weights that tell the moves apart, for some of them to be exploratory.
+        let mut evaluator = Counted::default();
+        evaluator.set_params(&(0..17).map(|i| i as f32 / 100.0).collect::<Vec<_>>());
+        for _ in 0..20 {
+            td.train_game(&mut evaluator, &mut rng);
+        }
+        // Each position gets an update, except the ones of exploratory moves.
+        assert!(td.explorer.exploratory > 0);
+        let steps = evaluator.gradients.get();
+        assert_eq!(evaluator.updates, steps - td.explorer.exploratory);
+    }
+
+    #[test]
+    fn replayed_exploratory_moves_are_not_learned() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut explorer = Explorer::new(Exploration::Greedy);
+        let mut game = explorer.play_game(&LinearEval0::default(), 0, &mut rng);
+        let td = TdLambda {
+            lambda: 0.7,
+            schedule: Schedule::Constant(1e-2),
+            explorer,
+            games: 0,
+        };
+
+        let mut evaluator = LinearEval0::default();
+        td.train_on(&mut evaluator, &game);
+        assert_ne!(evaluator.params(), LinearEval0::default().params());
+
+        // Only the outcome of the game moves the zero weights, and the last move
+        // leading to it is exploratory.
+        *game.exploratory.last_mut().unwrap() = true;
+        let mut evaluator = LinearEval0::default();
+        td.train_on(&mut evaluator, &game);
+        assert_eq!(evaluator.params(), LinearEval0::default().params());
     }
 }
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use rand::Rng;
+
+use crate::reinforcement_learning::*;
+use crate::checkpoint::game_rng;
+
+// Parallel self-play for TD(λ): worker threads play games with a frozen copy
+// of the evaluator and send them to the learner, which trains the evaluator on
+// them. Every `sync` games, the learner publishes its weights, and each worker
+// takes them before its next game.
+//
+// The game number i is played by the worker i mod nb_workers with the random
+// generator `game_rng(seed, i)`, so the games of a worker only depend on the
+// weights it was given. The order in which the learner receives the games of
+// the different workers is not deterministic.
+pub struct ParallelSelfPlay {
+    pub nb_workers: u32,
+    // The number of games between two weight syncs.
+    pub sync: u32,
+    // The number of games of the replay buffer.
+    pub capacity: usize,
+    // The number of games of the replay buffer the learner trains on after
+    // each new game.
+    pub replay: u32,
+}
+
+// The most recent games, to train on them again.
+pub struct ReplayBuffer {
+    games: VecDeque<Game>,
+    capacity: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> ReplayBuffer {
+        ReplayBuffer { games: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    // Adds a game, and forgets the oldest one if the buffer is full.
+    pub fn push(&mut self, game: Game) {
+        if self.games.len() == self.capacity {
+            self.games.pop_front();
+        }
+        self.games.push_back(game);
+    }
+
+    // A game chosen uniformly, or None if the buffer is empty.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<&Game> {
+        if self.games.is_empty() {
+            return None;
+        }
+        self.games.get(rng.gen_range(0..self.games.len()))
+    }
+}
+
+// A game sent by a worker, with the counts of its explorer.
+struct Played {
+    game: Game,
+    moves: u64,
+    exploratory: u64,
+}
+
+impl ParallelSelfPlay {
+    // Trains the evaluator until `trainer.games` reaches `nb_games`. The
+    // counts of the exploratory moves of the workers are added to the explorer
+    // of the trainer, and `progress` is called after each new game with this
+    // game.
+    pub fn run<T, F>(
+        &self,
+        trainer: &mut TdLambda,
+        evaluator: &mut T,
+        seed: u64,
+        nb_games: u32,
+        mut progress: F
+    )
+    where
+        T: TrainableHeuristic + Clone + Send + Sync,
+        F: FnMut(&T, &mut TdLambda, &Game)
+    {
+        let weights = RwLock::new(evaluator.params());
+        let version = AtomicU32::new(0);
+        let (weights, version) = (&weights, &version);
+        let exploration = trainer.explorer.exploration;
+        let first = trainer.games + 1;
+        // The workers cannot get more than about `sync` games ahead of the
+        // learner, so they play with weights at most two syncs old.
+        let (tx, rx) = mpsc::sync_channel(self.sync as usize);
+
+        thread::scope(|s| {
+            for worker in 0..self.nb_workers {
+                let tx = tx.clone();
+                let mut h = evaluator.clone();
+                let mut explorer = Explorer::new(exploration);
+                s.spawn(move || {
+                    let mut seen = 0;
+                    let mut game = first + worker;
+                    while game <= nb_games {
+                        let latest = version.load(Ordering::Acquire);
+                        if latest != seen {
+                            h.set_params(&weights.read().unwrap());
+                            seen = latest;
+                        }
+                        let mut rng = game_rng(seed, game);
+                        explorer.reset();
+                        let played = Played {
+                            game: explorer.play_game(&h, game - 1, &mut rng),
+                            moves: explorer.moves,
+                            exploratory: explorer.exploratory,
+                        };
+                        if tx.send(played).is_err() {
+                            return;
+                        }
+                        game += self.nb_workers;
+                    }
+                });
+            }
+            drop(tx);
+
+            // The learner.
+            let mut buffer = ReplayBuffer::new(self.capacity);
+            let mut rng = game_rng(seed, 0);
+            for played in rx {
+                trainer.explorer.moves += played.moves;
+                trainer.explorer.exploratory += played.exploratory;
+                trainer.train_on(evaluator, &played.game);
+                for _ in 0..self.replay {
+                    if let Some(game) = buffer.sample(&mut rng) {
+                        trainer.train_on(evaluator, game);
+                    }
+                }
+                trainer.games += 1;
+                if trainer.games.is_multiple_of(self.sync) {
+                    *weights.write().unwrap() = evaluator.params();
+                    version.fetch_add(1, Ordering::Release);
+                }
+                progress(evaluator, trainer, &played.game);
+                buffer.push(played.game);
+            }
+        });
+    }
+}
@@ -9,6 +9,18 @@ pub trait Heuristic {
     fn victory() -> f32; // The special value for the victory.
     fn eval(&self, board: &Board) -> f32;
     // fn move(&self, board: &Board);
+    // The exact value of the board, if it is known (see `tablebase`). The
+    // searches stop at the boards whose value is known.
+    fn exact(&self, _board: &Board) -> Option<f32> {
+        None
+    }
+}
+
+// A heuristic that can also give the probability that the player that is
+// supposed to play wins, in [0, 1]. Unlike the evaluations, these
+// probabilities are comparable between heuristics.
+pub trait WinProbability: Heuristic {
+    fn win_probability(&self, board: &Board) -> f32;
 }
 
 // The heuristic currently used on the website. This heuristic is the
@@ -39,9 +51,77 @@ impl Heuristic for SimpleHeuristic {
     }
 }
 
+// How the depth of a search is counted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DepthUnit {
+    // Each dice roll consumes one level of depth.
+    Rolls,
+    // Only the changes of player consume one level of depth: the bonus turns
+    // given by the rosettas are searched at the same depth, as a quiescence
+    // extension. The extension always ends, since each bonus turn moves a
+    // piece forward.
+    Turns,
+}
+
+// The options of the expectimax search.
+#[derive(Clone, Copy)]
+pub struct SearchOptions {
+    pub depth_unit: DepthUnit,
+    // The maximum number of rolls searched beyond the depth while the position
+    // is not quiet (see `is_quiet`). 0 disables the quiescence search.
+    pub quiescence: u32,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            depth_unit: DepthUnit::Rolls,
+            quiescence: 0,
+        }
+    }
+}
+
+// A position is quiet if the player that is to play cannot take a piece nor
+// land on the central rosetta, whatever the dice. The heuristics are not
+// reliable on the other positions, since they ignore the pieces that are about
+// to be taken in the central row. The other rosettas are not considered: a
+// piece can land on them, e.g. by entering with a 4, in almost every position.
+pub fn is_quiet(board: &Board) -> bool {
+    let adv = 1 - board.turn;
+    for dice in 1..=4 {
+        for place in board.possible_moves(dice) {
+            let dest = if place == ENTER { dice - 1 } else { place + dice };
+            if dest == CENTRAL_ROSETTA || (is_central(dest) && board.cells[adv][dest]) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
 pub fn expectimax<H: Heuristic>(h: &H, board: &Board, depth: u32) -> f32 {
+    expectimax_with(h, board, depth, &SearchOptions::default())
+}
+
+pub fn expectimax_with<H>(
+    h: &H,
+    board: &Board,
+    depth: u32,
+    opts: &SearchOptions
+) -> f32
+where H: Heuristic
+{
+    if let Some(value) = h.exact(board) {
+        return value;
+    }
     if depth == 0 {
-        return h.eval(board);
+        if opts.quiescence == 0 || is_quiet(board) {
+            return h.eval(board);
+        }
+        // Extends the search by one roll.
+        let opts = SearchOptions { quiescence: opts.quiescence - 1, ..*opts };
+        return expectimax_with(h, board, 1, &opts);
     }
 
     let mut res = 0.0;
@@ -49,12 +129,12 @@ pub fn expectimax<H: Heuristic>(h: &H, board: &Board, depth: u32) -> f32 {
         let moves = board.possible_moves(dice);
         let mut max;
         if moves.is_empty() {
-            max = eval_no_move(h, board, depth - 1);
+            max = eval_no_move(h, board, depth - 1, opts);
         }
         else {
-            max = eval_move(h, board, dice, moves[0], depth - 1);
+            max = eval_move_with(h, board, dice, moves[0], depth - 1, opts);
             for &place in moves.iter().skip(1) {
-                let val = eval_move(h, board, dice, place, depth - 1);
+                let val = eval_move_with(h, board, dice, place, depth - 1, opts);
                 if val > max {
                     max = val;
                 }
@@ -77,24 +157,75 @@ pub fn eval_move<H>(
     depth: u32
 ) -> f32
 where H: Heuristic
+{
+    eval_move_with(h, board, dice, place, depth, &SearchOptions::default())
+}
+
+pub fn eval_move_with<H>(
+    h: &H,
+    board: &Board,
+    dice: usize,
+    place: usize,
+    depth: u32,
+    opts: &SearchOptions
+) -> f32
+where H: Heuristic
 {
     let mut copy = board.clone();
     if copy.perform_move(dice, place) {
         return H::victory();
     }
-    let val = expectimax(h, &copy, depth);
     if copy.turn == board.turn {
-        val
+        // A bonus turn: the roll that led to it is given back when the depth
+        // is counted in turns.
+        let depth = match opts.depth_unit {
+            DepthUnit::Rolls => depth,
+            DepthUnit::Turns => depth + 1,
+        };
+        expectimax_with(h, &copy, depth, opts)
     }
     else {
-        -val
+        -expectimax_with(h, &copy, depth, opts)
     }
 }
 
+// Given a dice roll, returns the move with the best expectimax evaluation and
+// its evaluation. There must be at least one possible move.
+pub fn best_move<H>(h: &H, board: &Board, dice: usize, depth: u32) -> (usize, f32)
+where H: Heuristic
+{
+    best_move_with(h, board, dice, depth, &SearchOptions::default())
+}
+
+pub fn best_move_with<H>(
+    h: &H,
+    board: &Board,
+    dice: usize,
+    depth: u32,
+    opts: &SearchOptions
+) -> (usize, f32)
+where H: Heuristic
+{
+    let moves = board.possible_moves(dice);
+    let mut best = moves[0];
+    let mut best_val = eval_move_with(h, board, dice, moves[0], depth, opts);
+    for &place in moves.iter().skip(1) {
+        let val = eval_move_with(h, board, dice, place, depth, opts);
+        if val > best_val {
+            best_val = val;
+            best = place;
+        }
+    }
+
+    (best, best_val)
+}
+
 // Return the evaluation of a board after a roll dice that prevents the player
 // from doing anything (for example, after a 0 roll).
-fn eval_no_move<H: Heuristic>(h: &H, board: &Board, depth: u32) -> f32 {
+fn eval_no_move<H>(h: &H, board: &Board, depth: u32, opts: &SearchOptions) -> f32
+where H: Heuristic
+{
     let mut copy = board.clone();
     copy.change_turn();
-    -expectimax(h, &copy, depth)
+    -expectimax_with(h, &copy, depth, opts)
 }
@@ -0,0 +1,107 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::tournament::*;
+use crate::reinforcement_learning::*;
+
+// Supervised training: positions are labeled with the values of a slow but
+// strong evaluation, such as a deep expectimax search, and a fast evaluator is
+// fitted to these values by minibatch regression.
+
+pub struct Sample {
+    pub board: Board,
+    pub target: f32,
+}
+
+// Samples positions from games where both sides are played by `player`. One
+// position out of `keep` is kept, so that the positions come from many
+// different games. Returns the positions and the number of games played.
+pub fn sample_positions<P, R>(player: &mut P, nb_positions: usize, keep: u32,
+                              rng: &mut R) -> (Vec<Board>, u32)
+where P: Player, R: Rng
+{
+    let mut positions = Vec::with_capacity(nb_positions);
+    let mut nb_games = 0;
+    while positions.len() < nb_positions {
+        let mut board = Board::default();
+        loop {
+            if rng.gen_range(0..keep) == 0 && positions.len() < nb_positions {
+                positions.push(board.clone());
+            }
+            let dice = roll_dices(rng);
+            let places = board.possible_moves(dice);
+            if places.is_empty() {
+                board.change_turn();
+                continue;
+            }
+            let place = if places.len() == 1 {
+                places[0]
+            }
+            else {
+                player.choose_move(&board, dice)
+            };
+            if board.perform_move(dice, place) {
+                break;
+            }
+        }
+        nb_games += 1;
+    }
+
+    (positions, nb_games)
+}
+
+// Labels each position with `value`, which gives the value of a board for the
+// player that is to play.
+pub fn label<F>(positions: Vec<Board>, value: F) -> Vec<Sample>
+where F: Fn(&Board) -> f32
+{
+    positions.into_iter().map(|board| {
+        let target = value(&board);
+        Sample { board, target }
+    }).collect()
+}
+
+// The value of a board given by an expectimax search, divided by the victory
+// value of the heuristic so that a win is worth 1. The values of the other
+// boards are in the units of the heuristic, e.g. they are not bounded for the
+// linear evaluation.
+pub fn expectimax_value<H: Heuristic>(h: &H, board: &Board, depth: u32) -> f32 {
+    expectimax(h, board, depth) / H::victory()
+}
+
+// The mean squared error of an evaluator on samples.
+pub fn mse<H: Heuristic>(h: &H, samples: &[Sample]) -> f32 {
+    let sum: f32 = samples.iter().map(|s| {
+        let diff = s.target - h.eval(&s.board);
+        diff * diff
+    }).sum();
+
+    sum / samples.len() as f32
+}
+
+// Does one epoch of minibatch gradient descent on the squared error: the
+// samples are shuffled, then the parameters move after each batch along the
+// mean gradient of the batch.
+pub fn fit_epoch<T, R>(
+    evaluator: &mut T,
+    samples: &mut [Sample],
+    batch_size: usize,
+    alpha: f32,
+    rng: &mut R
+)
+where T: TrainableHeuristic, R: Rng
+{
+    samples.shuffle(rng);
+    for batch in samples.chunks(batch_size) {
+        let mut step = vec![0.0; evaluator.params().len()];
+        for s in batch {
+            let diff = s.target - evaluator.eval(&s.board);
+            for (x, g) in step.iter_mut().zip(evaluator.gradient(&s.board)) {
+                *x += diff * g;
+            }
+        }
+        evaluator.add_scaled(&step, alpha / batch.len() as f32);
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use crate::game_manager::*;
+use crate::strategy::*;
+use crate::weights::write_atomically;
+
+// An endgame tablebase: the exact probability of winning of the player that
+// is to play, for all the positions where each player has at most `pieces`
+// pieces that are not out of the board.
+//
+// The moves can only keep the pieces on the board or move them out, so these
+// positions only lead to each other. But the captures make the games loop, so
+// the probabilities are found by value iteration instead of going back from
+// the end.
+//
+// Tablebase files are made of a text header:
+//
+// ur-tablebase <version>
+// pieces <maximum number of pieces not out>
+// rules <rule variant>
+//
+// followed by the probabilities as little-endian f32, in the order of the
+// positions of the table.
+pub struct Tablebase {
+    pub pieces: u8,
+    // The possible sides of a position for one player: the number of pieces
+    // ready, and the set of the cells of its pieces.
+    sides: Vec<(u8, u16)>,
+    // The index in `sides` of each (ready << 14 | cells), or NONE.
+    index: Vec<u32>,
+    // The probabilities, indexed by `position`.
+    values: Vec<f32>,
+}
+
+pub const VERSION: u32 = 1;
+
+// The largest number of pieces of a table. The table has 2 * sides^2
+// positions, where sides grows quickly with the number of pieces: 4 pieces
+// already take a few minutes to solve.
+pub const MAX_PIECES: u8 = 4;
+
+const NONE: u32 = u32::MAX;
+
+// The value iteration stops when no probability changes by more than this.
+const PRECISION: f32 = 1e-6;
+
+fn cells_mask(cells: &[bool; 14]) -> u16 {
+    let mut mask = 0;
+    for (i, &piece) in cells.iter().enumerate() {
+        if piece {
+            mask |= 1 << i;
+        }
+    }
+
+    mask
+}
+
+fn invalid(path: &str, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+}
+
+impl Tablebase {
+    // An empty table, with all probabilities to 0.5.
+    fn new(pieces: u8) -> Tablebase {
+        let mut sides = Vec::new();
+        let mut index = vec![NONE; 8 << 14];
+        for ready in 0..=pieces {
+            for mask in 0..1u16 << 14 {
+                let n = ready as u32 + mask.count_ones();
+                if n >= 1 && n <= pieces as u32 {
+                    index[(ready as usize) << 14 | mask as usize] = sides.len() as u32;
+                    sides.push((ready, mask));
+                }
+            }
+        }
+        let values = vec![0.5; 2 * sides.len() * sides.len()];
+
+        Tablebase { pieces, sides, index, values }
+    }
+
+    // The number of positions of the table, some of which are not valid.
+    pub fn nb_positions(&self) -> usize {
+        self.values.len()
+    }
+
+    fn side(&self, board: &Board, player: usize) -> Option<usize> {
+        let ready = board.ready[player];
+        let cells = cells_mask(&board.cells[player]);
+        let n = ready + cells.count_ones() as u8;
+        if n > 7 || board.out[player] != 7 - n {
+            return None;
+        }
+        match self.index[(ready as usize) << 14 | cells as usize] {
+            NONE => None,
+            i => Some(i as usize),
+        }
+    }
+
+    // The index of a board in the table, or None if it is not in the table.
+    pub fn position(&self, board: &Board) -> Option<usize> {
+        let n = self.sides.len();
+        let sides = [self.side(board, 0)?, self.side(board, 1)?];
+        Some((board.turn * n + sides[0]) * n + sides[1])
+    }
+
+    // The board of an index of the table, or None if both players would have
+    // a piece on the same cell.
+    fn board(&self, position: usize) -> Option<Board> {
+        let n = self.sides.len();
+        let mut board = Board { turn: position / (n * n), ..Board::default() };
+        let sides = [(position / n) % n, position % n];
+        for (player, &side) in sides.iter().enumerate() {
+            let (ready, mask) = self.sides[side];
+            board.ready[player] = ready;
+            for (i, cell) in board.cells[player].iter_mut().enumerate() {
+                *cell = mask & (1 << i) != 0;
+            }
+            board.out[player] = 7 - ready - mask.count_ones() as u8;
+        }
+        let central = (0..14).filter(|&i| is_central(i))
+            .any(|i| board.cells[0][i] && board.cells[1][i]);
+
+        if central { None } else { Some(board) }
+    }
+
+    // The probability of winning of the player that is to play, or None if
+    // the board is not in the table.
+    pub fn probe(&self, board: &Board) -> Option<f32> {
+        self.position(board).map(|i| self.values[i])
+    }
+
+    // The expected probability of winning of a board over the dice rolls,
+    // with the probabilities of the other positions taken from the table.
+    fn expected(&self, board: &Board) -> f32 {
+        let value = |next: &Board| self.values[self.position(next).unwrap()];
+        let mut res = 0.0;
+        for (dice, &proba) in PROBABILITIES.iter().enumerate() {
+            let moves = board.possible_moves(dice);
+            let mut max = 0.0f32;
+            if moves.is_empty() {
+                let mut copy = board.clone();
+                copy.change_turn();
+                max = 1.0 - value(&copy);
+            }
+            for place in moves {
+                let mut copy = board.clone();
+                let val =
+                    if copy.perform_move(dice, place) { 1.0 }
+                    else if copy.turn == board.turn { value(&copy) }
+                    else { 1.0 - value(&copy) };
+                max = max.max(val);
+            }
+            res += proba * max;
+        }
+
+        res
+    }
+
+    // Solves all the positions where each player has at most `pieces` pieces
+    // that are not out of the board.
+    pub fn generate(pieces: u8) -> Tablebase {
+        let mut table = Tablebase::new(pieces);
+        let boards: Vec<(usize, Board)> = (0..table.nb_positions())
+            .filter_map(|i| table.board(i).map(|board| (i, board)))
+            .collect();
+        loop {
+            let mut change = 0.0f32;
+            for (i, board) in &boards {
+                let value = table.expected(board);
+                change = change.max((value - table.values[*i]).abs());
+                table.values[*i] = value;
+            }
+            if change < PRECISION {
+                break;
+            }
+        }
+
+        table
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut bytes = format!("ur-tablebase {}\npieces {}\nrules {}\n",
+                                VERSION, self.pieces, RULES).into_bytes();
+        for value in &self.values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        write_atomically(path, bytes)
+    }
+
+    // Reads a table written by `save`. Fails if it was written for other rules.
+    pub fn load(path: &str) -> io::Result<Tablebase> {
+        let bytes = fs::read(path)?;
+        let mut header = Vec::new();
+        let mut start = 0;
+        for _ in 0..3 {
+            let end = bytes[start..].iter().position(|&b| b == b'\n')
+                .ok_or_else(|| invalid(path, "incomplete header"))?;
+            let line = String::from_utf8_lossy(&bytes[start..start + end]);
+            header.push(line.to_string());
+            start += end + 1;
+        }
+        let field = |i: usize, name: &str| {
+            header[i].strip_prefix(name)
+                .and_then(|l| l.strip_prefix(' '))
+                .ok_or_else(|| invalid(path, &format!("missing field {}", name)))
+        };
+
+        let version = field(0, "ur-tablebase")?;
+        if version != VERSION.to_string() {
+            return Err(invalid(path, &format!("unsupported version {}", version)));
+        }
+        let pieces: u8 = field(1, "pieces")?.parse().ok()
+            .filter(|p| (1..=MAX_PIECES).contains(p))
+            .ok_or_else(|| invalid(path, "invalid number of pieces"))?;
+        let rules = field(2, "rules")?;
+        if rules != RULES {
+            return Err(invalid(path, &format!("solved for the rules {}", rules)));
+        }
+
+        let mut table = Tablebase::new(pieces);
+        let body = &bytes[start..];
+        if body.len() != 4 * table.nb_positions() {
+            return Err(invalid(path, "wrong number of positions"));
+        }
+        for (value, chunk) in table.values.iter_mut().zip(body.chunks(4)) {
+            *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        Ok(table)
+    }
+}
+
+// A heuristic whose values are exact for the positions of the tablebase, and
+// those of `h` elsewhere. The exact values are 2p - 1 times the victory value,
+// where p is the probability of winning, so `h` should be calibrated (see
+// `Calibrated`) for both kinds of values to be comparable.
+pub struct Tablebased<H: Heuristic> {
+    pub h: H,
+    pub table: Arc<Tablebase>,
+}
+
+impl<H: Heuristic + Clone> Clone for Tablebased<H> {
+    fn clone(&self) -> Self {
+        Tablebased { h: self.h.clone(), table: self.table.clone() }
+    }
+}
+
+impl<H: Heuristic> Heuristic for Tablebased<H> {
+    fn victory() -> f32 {
+        H::victory()
+    }
+
+    fn eval(&self, board: &Board) -> f32 {
+        self.exact(board).unwrap_or_else(|| self.h.eval(board))
+    }
+
+    fn exact(&self, board: &Board) -> Option<f32> {
+        self.table.probe(board).map(|p| (2.0 * p - 1.0) * H::victory())
+    }
+}
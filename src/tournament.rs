@@ -1,73 +1,158 @@
-use rand::thread_rng;
+use rand::{Rng, thread_rng};
 use crate::game_manager::*;
+use crate::record::{Forfeit, Record, move_notation};
 
 pub trait Player {
     fn choose_move(&mut self, board: &Board, dice: usize) -> usize;
 }
 
+// Plays the game from `board` until the end, both sides choosing their moves
+// with `policy`. The dices are rolled with `rng`.
+// Returns the index of the winner, or the forfeit of the first side for which
+// `policy` chooses an illegal move.
+pub fn play_out<P, R>(board: &Board, policy: &mut P, rng: &mut R)
+    -> Result<usize, Forfeit>
+where P: Player, R: Rng
+{
+    let mut board = board.clone();
+    loop {
+        let dice = roll_dices(rng);
+        let places = board.possible_moves(dice);
+        if places.is_empty() {
+            board.change_turn();
+            continue;
+        }
+        let place = if places.len() == 1 {
+            places[0]
+        }
+        else {
+            let place = policy.choose_move(&board, dice);
+            if !places.contains(&place) {
+                let error = board.check_move(dice, place).unwrap_err();
+                return Err(Forfeit { player: board.turn, dice, place, error });
+            }
+            place
+        };
+        if board.perform_move(dice, place) {
+            return Ok(board.turn);
+        }
+    }
+}
+
 // Plays 2 * nb_games games between two players.
 // Each player the same number of games as the light and the blue player.
 // Returns the array [a, b] where a and b are the number of games won by
 // players a and b.
 // TODO: returns the number of games won when playing light or blue.
-pub fn showdown(
+pub fn showdown(player_a: impl Player, player_b: impl Player, nb_games: u32)
+    -> [u32; 2] {
+    showdown_observed(player_a, player_b, nb_games, |_| {})
+}
+
+// Same as `showdown`, and gives the record of each game to `observe`, e.g. to
+// add it to a `Database`. A player that chooses an illegal move loses the game
+// (see `Record::play`), and the forfeit is reported.
+pub fn showdown_observed(
     mut player_a: impl Player,
     mut player_b: impl Player,
-    nb_games: u32
+    nb_games: u32,
+    mut observe: impl FnMut(&Record)
 ) -> [u32; 2] {
     let mut res = [0, 0];
     let mut rng = thread_rng();
     for _ in 0..nb_games {
-        let mut board = Board::default();
-        loop {
-            // Plays a game where player_a starts.
-            let dice = roll_dices(&mut rng);
-            let places = board.possible_moves(dice);
-            if places.is_empty() {
-                board.change_turn();
-                continue;
-            }
-            let place = if places.len() == 1 {
-                // No need to call a choice function if there is only one
-                // possible move.
-                places[0]
-            }
-            else if board.turn == 0 {
-                player_a.choose_move(&board, dice)
-            }
-            else {
-                player_b.choose_move(&board, dice)
-            };
-            assert!(places.contains(&place));
-            if board.perform_move(dice, place) {
-                res[board.turn] += 1;
-                break;
-            }
+        // Plays a game where player_a starts, then a game where player_b
+        // starts.
+        let record = Record::play(Board::default(), &mut player_a, &mut player_b,
+                                  &mut rng);
+        report_forfeit(&record, ["A", "B"]);
+        res[record.winner().unwrap()] += 1;
+        observe(&record);
+
+        let record = Record::play(Board::default(), &mut player_b, &mut player_a,
+                                  &mut rng);
+        report_forfeit(&record, ["B", "A"]);
+        res[1 - record.winner().unwrap()] += 1;
+        observe(&record);
+    }
+
+    res
+}
+
+// Prints the illegal move of a game lost by forfeit, with the names of the
+// first and the second player.
+fn report_forfeit(record: &Record, names: [&str; 2]) {
+    if let Some(forfeit) = &record.forfeit {
+        let player = names[(forfeit.player != record.start.turn) as usize];
+        eprintln!("Player {} forfeits a game: {} with a dice of {}: {}", player,
+                  move_notation(Some(forfeit.place)), forfeit.dice, forfeit.error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // Plays the first possible move.
+    struct First;
+
+    impl Player for First {
+        fn choose_move(&mut self, board: &Board, dice: usize) -> usize {
+            board.possible_moves(dice)[0]
         }
+    }
 
-        loop {
-            let dice = roll_dices(&mut rng);
-            let places = board.possible_moves(dice);
-            if places.is_empty() {
-                board.change_turn();
-                continue;
-            }
-            let place = if places.len() == 1 {
-                places[0]
-            }
-            else if board.turn == 0 {
-                player_b.choose_move(&board, dice)
-            }
-            else {
-                player_a.choose_move(&board, dice)
+    // Always plays a move that is not on the board.
+    struct Cheater;
+
+    impl Player for Cheater {
+        fn choose_move(&mut self, _: &Board, _: usize) -> usize {
+            ENTER + 1
+        }
+    }
+
+    // Records the side to play, then plays like `Cheater`.
+    struct Traitor(Option<usize>);
+
+    impl Player for Traitor {
+        fn choose_move(&mut self, board: &Board, _: usize) -> usize {
+            self.0.get_or_insert(board.turn);
+            ENTER + 1
+        }
+    }
+
+    #[test]
+    fn illegal_rollouts_forfeit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut traitor = Traitor(None);
+            let forfeit = match play_out(&Board::default(), &mut traitor, &mut rng) {
+                Ok(_) => panic!("The game should be forfeited"),
+                Err(forfeit) => forfeit,
             };
-            assert!(places.contains(&place));
-            if board.perform_move(dice, place) {
-                res[1 - board.turn] += 1;
-                break;
-            }
+            assert_eq!(Some(forfeit.player), traitor.0);
+            assert_eq!(forfeit.place, ENTER + 1);
+            assert_eq!(forfeit.error, IllegalMove::InvalidPlace(ENTER + 1));
         }
     }
 
-    res
+    #[test]
+    fn illegal_moves_forfeit() {
+        let mut nb_records = 0;
+        let res = showdown_observed(First, Cheater, 20, |record| {
+            let forfeit = record.forfeit.as_ref().unwrap();
+            assert_eq!(record.winner(), Some(1 - forfeit.player));
+            let board = record.positions().pop().unwrap();
+            assert_eq!(board.check_move(forfeit.dice, ENTER + 1), Err(forfeit.error));
+
+            let parsed = Record::parse(&record.to_text()).unwrap();
+            assert_eq!(parsed.to_text(), record.to_text());
+            assert_eq!(parsed.winner(), record.winner());
+            nb_records += 1;
+        });
+        assert_eq!(res, [40, 0]);
+        assert_eq!(nb_records, 40);
+    }
 }
@@ -1,50 +1,566 @@
 extern crate rand;
-use rand::thread_rng;
+use std::env;
+use rand::{Rng, SeedableRng, thread_rng};
+use rand::rngs::StdRng;
 
-mod game_manager;
-use game_manager::*;
+use ur::game_manager::*;
+use ur::strategy::*;
+use ur::reinforcement_learning::*;
+use ur::neural::*;
+use ur::weights::*;
+use ur::checkpoint::*;
+use ur::players::*;
+use ur::benchmark::*;
+use ur::supervised::*;
+use ur::self_play::*;
+use ur::database::*;
 
-mod strategy;
-use strategy::*;
+const ALPHA: f32 = 1e-4;
+const LAMBDA: f32 = 0.7;
+// The initial learning coefficients of TD(λ) and their decays. The networks
+// need a higher coefficient, which decreases more slowly.
+const TD_LAMBDA_ALPHA: f32 = 1e-3;
+const TD_LAMBDA_DECAY: f32 = 1e-3;
+const MLP_ALPHA: f32 = 1e-2;
+const MLP_DECAY: f32 = 1e-4;
+const NB_GAMES: u32 = 10000;
+// The number of games between two checkpoints.
+const CHECKPOINT: u32 = 1000;
+// The default number of games between two benchmarks.
+const BENCHMARK: u32 = 5000;
+// The number of held-out positions, and of games of each benchmark match as
+// each color.
+const BENCHMARK_POSITIONS: usize = 1000;
+const BENCHMARK_GAMES: u32 = 100;
+const BENCHMARK_DEPTH: u32 = 2;
+// The decay of the exploration rate and temperature, see `parse_exploration`.
+const EXPLORATION_DECAY: f32 = 1e-4;
+// The defaults of the supervised training: number of positions, depth of the
+// expectimax search giving the targets, and number of epochs.
+const SUPERVISED_POSITIONS: usize = 10000;
+const SUPERVISED_DEPTH: u32 = 3;
+const SUPERVISED_EPOCHS: u32 = 20;
+const BATCH_SIZE: usize = 32;
+// The parallel self-play: number of games between two weight syncs, number of
+// games of the replay buffer, and number of replayed games after each new one.
+const SYNC: u32 = 100;
+const REPLAY_CAPACITY: usize = 1000;
+const REPLAY: u32 = 1;
+// One position out of ten of the sampled games is used, and one sample out of
+// ten is held out to measure the error of the evaluator.
+const SUPERVISED_KEEP: u32 = 10;
+const VALIDATION: usize = 10;
+// The default weight file of the evaluation of the searches giving the targets
+// of the supervised training, relative to the working directory.
+const TEACHER_PATH: &str = "weights/linear_eval0.txt";
 
-mod reinforcement_learning;
-use reinforcement_learning::*;
+struct Config {
+    // "td0", "td-lambda" or "supervised".
+    mode: String,
+    // The kind of evaluator, e.g. "linear".
+    model: String,
+    // The total number of games of the run.
+    nb_games: u32,
+    // The initial learning coefficient of TD(λ).
+    alpha: f32,
+    // The weight file written at each checkpoint.
+    out: String,
+    // The checkpoint file written at each checkpoint.
+    checkpoint: String,
+    seed: u64,
+    // The number of games of the checkpoint the run is resumed from, if it is
+    // resumed instead of started from scratch.
+    resume: Option<u32>,
+    // The number of games between two benchmarks, 0 to disable them.
+    benchmark: u32,
+    // The CSV file where the results of the benchmarks are appended.
+    curve: String,
+    // The exploration policy, see `parse_exploration`.
+    exploration: String,
+    // The number of self-play threads of TD(λ), 0 to play and learn in the
+    // same thread.
+    workers: u32,
+    // The position database where the self-play games are added at each
+    // checkpoint, if any.
+    database: Option<String>,
+    // The weight file of the evaluation of the searches giving the targets of
+    // the supervised training, the number of positions, the depth of the
+    // searches and the number of epochs.
+    teacher: String,
+    positions: usize,
+    depth: u32,
+    epochs: u32,
+}
 
-const ALPHA: f32 = 1e-4;
+// Parses "greedy", "epsilon:<rate>" or "softmax:<temperature>", where the
+// rate is between 0 and 1 and the temperature is positive. The rate and the
+// temperature decrease as the training goes on.
+fn parse_exploration(s: &str) -> Option<Exploration> {
+    if s == "greedy" {
+        return Some(Exploration::Greedy);
+    }
+    let (name, value) = s.split_once(':')?;
+    let initial: f32 = value.parse().ok()?;
+    let schedule = Schedule::InverseTime { initial, decay: EXPLORATION_DECAY };
+    match name {
+        "epsilon" if (0.0..=1.0).contains(&initial) =>
+            Some(Exploration::EpsilonGreedy(schedule)),
+        "softmax" if initial > 0.0 && initial.is_finite() =>
+            Some(Exploration::Softmax(schedule)),
+        _ => None,
+    }
+}
 
+// Usage:
+// training [td0 | td-lambda] [linear | tanh | features | mlp | mlp-rich]
+//          [--games <number of games>] [--out <weight file>] [--seed <seed>]
+//          [--benchmark <games between benchmarks>] [--curve <CSV file>]
+//          [--explore greedy | epsilon:<rate> | softmax:<temperature>]
+//          [--workers <number of self-play threads>]
+//          [--database <position database>]
+// training --resume <checkpoint file> [--games <total number of games>]
+//          [--benchmark <games between benchmarks>]
+// training supervised [linear | tanh | features | mlp | mlp-rich]
+//          [--teacher <weight file>] [--positions <number of positions>]
+//          [--depth <search depth>]
+//          [--epochs <number of epochs>] [--out <weight file>] [--seed <seed>]
+//
+// The weights are written to <model>.txt by default, the state of the run
+// to <model>.checkpoint and the learning curve to <model>.csv.
 fn main() {
-    let mut evaluator = LinearEval0::default();
-    let mut rng = thread_rng();
+    let args: Vec<String> = env::args().collect();
+    let positional: Vec<&str> = args.iter().skip(1)
+        .take_while(|a| !a.starts_with("--"))
+        .map(|a| a.as_str())
+        .collect();
+    let mode = positional.first().copied().unwrap_or("td0");
+    let model = positional.get(1).copied().unwrap_or("linear");
+    let alpha = if model.starts_with("mlp") { MLP_ALPHA } else { TD_LAMBDA_ALPHA };
+    let mut config = Config {
+        mode: mode.to_string(),
+        model: model.to_string(),
+        nb_games: NB_GAMES,
+        alpha,
+        out: format!("{}.txt", model),
+        checkpoint: format!("{}.checkpoint", model),
+        seed: thread_rng().gen(),
+        resume: None,
+        benchmark: BENCHMARK,
+        curve: format!("{}.csv", model),
+        exploration: "greedy".to_string(),
+        workers: 0,
+        teacher: TEACHER_PATH.to_string(),
+        database: None,
+        positions: SUPERVISED_POSITIONS,
+        depth: SUPERVISED_DEPTH,
+        epochs: SUPERVISED_EPOCHS,
+    };
+
+    // The options given with --resume override the ones of the checkpoint, so
+    // the checkpoint is read after all the options.
+    let mut resume = None;
+    let mut games_given = false;
+    let mut benchmark_given = false;
+    let mut seed_given = false;
+    let mut options = args.iter().skip(1 + positional.len());
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("Missing value for {}", option);
+                return;
+            }
+        };
+        match option.as_str() {
+            "--games" => match value.parse() {
+                Ok(n) => {
+                    config.nb_games = n;
+                    games_given = true;
+                }
+                Err(_) => {
+                    eprintln!("Invalid number of games: {}", value);
+                    return;
+                }
+            },
+            "--out" => config.out = value.clone(),
+            "--curve" => config.curve = value.clone(),
+            "--explore" => config.exploration = value.clone(),
+            "--teacher" => config.teacher = value.clone(),
+            "--database" => config.database = Some(value.clone()),
+            "--benchmark" => match value.parse() {
+                Ok(n) => {
+                    config.benchmark = n;
+                    benchmark_given = true;
+                }
+                Err(_) => {
+                    eprintln!("Invalid number of games: {}", value);
+                    return;
+                }
+            },
+            "--workers" => match value.parse() {
+                Ok(n) => config.workers = n,
+                Err(_) => {
+                    eprintln!("Invalid number of workers: {}", value);
+                    return;
+                }
+            },
+            // At least one position is held out for validation.
+            "--positions" => match value.parse() {
+                Ok(n) if n >= VALIDATION => config.positions = n,
+                _ => {
+                    eprintln!("Invalid number of positions: {}", value);
+                    return;
+                }
+            },
+            "--depth" => match value.parse() {
+                Ok(depth) => config.depth = depth,
+                Err(_) => {
+                    eprintln!("Invalid depth: {}", value);
+                    return;
+                }
+            },
+            "--epochs" => match value.parse() {
+                Ok(n) => config.epochs = n,
+                Err(_) => {
+                    eprintln!("Invalid number of epochs: {}", value);
+                    return;
+                }
+            },
+            "--seed" => match value.parse() {
+                Ok(seed) => {
+                    config.seed = seed;
+                    seed_given = true;
+                }
+                Err(_) => {
+                    eprintln!("Invalid seed: {}", value);
+                    return;
+                }
+            },
+            "--resume" => resume = Some(value.clone()),
+            _ => {
+                eprintln!("Unknown option: {}", option);
+                return;
+            }
+        }
+    }
+
+    if let Some(path) = resume {
+        // A resumed run must go on with the same random generators.
+        if seed_given {
+            eprintln!("The seed of a resumed run cannot be changed");
+            return;
+        }
+        match Checkpoint::load(&path) {
+            Ok(cp) => {
+                config.mode = cp.mode;
+                config.model = cp.model;
+                config.alpha = cp.alpha;
+                config.out = cp.weights;
+                config.curve = cp.curve;
+                config.exploration = cp.exploration;
+                config.workers = cp.workers;
+                config.database = cp.database;
+                config.checkpoint = path;
+                config.seed = cp.seed;
+                if !games_given {
+                    config.nb_games = cp.nb_games;
+                }
+                if !benchmark_given {
+                    config.benchmark = cp.benchmark;
+                }
+                config.resume = Some(cp.games);
+            }
+            Err(e) => {
+                eprintln!("Cannot read the checkpoint: {}", e);
+                return;
+            }
+        }
+    }
+
+    // The initial weights of the networks also depend on the seed of the run.
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    match config.model.as_str() {
+        "linear" => start(&config, LinearEval0::default),
+        "tanh" => start(&config, TanhEval0::default),
+        "features" => start(&config, FeatureEval::default),
+        "mlp" => start(&config, || {
+            Mlp::new(&[40], Output::Sigmoid, Encoding::Board, &mut rng)
+        }),
+        "mlp-rich" => start(&config, || {
+            Mlp::new(&[40], Output::Sigmoid, Encoding::Rich, &mut rng)
+        }),
+        model => eprintln!("Unknown model: {}", model),
+    }
+}
+
+// Trains a new evaluator, or resumes the training of the evaluator of the
+// weight file after the games of the checkpoint.
+fn start<T>(config: &Config, new: impl FnOnce() -> T)
+where T: Persistent + Clone + Send + Sync
+{
+    let (evaluator, games) = match config.resume {
+        Some(games) => match load(&config.out) {
+            Ok((h, _)) => (h, games),
+            Err(e) => {
+                eprintln!("Cannot read the weights: {}", e);
+                return;
+            }
+        },
+        None => (new(), 0),
+    };
+    let exploration = match parse_exploration(&config.exploration) {
+        Some(exploration) => exploration,
+        None => {
+            eprintln!("Invalid exploration: {}", config.exploration);
+            return;
+        }
+    };
+    let explorer = Explorer::new(exploration);
+    if let Err(e) = truncate_csv(&config.curve, games) {
+        eprintln!("Cannot update the learning curve: {}", e);
+        return;
+    }
+    let bench = Benchmark::new(BENCHMARK_POSITIONS, BENCHMARK_GAMES, BENCHMARK_DEPTH);
+    match config.mode.as_str() {
+        "td0" => train_td0(config, &bench, explorer, evaluator, games),
+        "td-lambda" => train_td_lambda(config, &bench, explorer, evaluator, games),
+        "supervised" => train_supervised(config, &bench, evaluator),
+        mode => eprintln!("Unknown mode: {}", mode),
+    }
+}
+
+// Adds the game that was just played to the position database, then runs the
+// checkpoints and the benchmarks that are due after `games` games.
+fn progress<T>(
+    config: &Config,
+    bench: &Benchmark,
+    explorer: &mut Explorer,
+    db: &mut Database,
+    evaluator: &T,
+    game: &Game,
+    games: u32
+)
+where T: Persistent + Clone
+{
+    if config.database.is_some() {
+        db.add_game(game);
+    }
+    if games.is_multiple_of(CHECKPOINT) || games == config.nb_games {
+        checkpoint(config, db, evaluator, games);
+        println!("Exploratory moves: {:.2}%", 100.0 * explorer.rate());
+        explorer.reset();
+    }
+    if config.benchmark > 0 && games.is_multiple_of(config.benchmark) {
+        let result = run_benchmark(bench, evaluator);
+        if let Err(e) = append_csv(&config.curve, games, &result) {
+            eprintln!("Cannot write the learning curve: {}", e);
+        }
+    }
+}
+
+// Runs the benchmark on the evaluator and prints its results.
+fn run_benchmark<T>(bench: &Benchmark, evaluator: &T) -> BenchmarkResult
+where T: Heuristic + Clone
+{
+    let result = bench.run(evaluator);
+    println!("Benchmark: {:.3} against Greedy, {:.3} against Advancement, \
+              TD error {}",
+             result.vs_greedy, result.vs_advancement, result.td_error);
+
+    result
+}
+
+// Adds the positions of the games played since the last checkpoint to the
+// position database, then displays the evaluator and writes it to the weight
+// file, and the state of the run to the checkpoint file. Each file is only
+// written if the previous ones were, and the positions are kept in `db` until
+// they are written. A run resumed after a crash replays the games played
+// since its checkpoint: their positions may be added twice to the database,
+// but they are never missing.
+fn checkpoint<T>(config: &Config, db: &mut Database, evaluator: &T, games: u32)
+where T: Persistent
+{
+    if let Some(path) = &config.database {
+        let result = Database::load(path).and_then(|mut saved| {
+            saved.merge(db);
+            saved.save(path)
+        });
+        if let Err(e) = result {
+            eprintln!("Cannot update the position database: {}", e);
+            return;
+        }
+        *db = Database::default();
+    }
+    println!("\nAfter {} games:", games);
+    evaluator.disp();
+    // TD(0) has a constant learning coefficient.
+    let alpha = if config.mode == "td0" { ALPHA } else { config.alpha };
+    if let Err(e) = save(evaluator, &Metadata::new(games, alpha), &config.out) {
+        eprintln!("Cannot write the weights: {}", e);
+        return;
+    }
+    let cp = Checkpoint {
+        mode: config.mode.clone(),
+        model: config.model.clone(),
+        games,
+        nb_games: config.nb_games,
+        seed: config.seed,
+        alpha: config.alpha,
+        weights: config.out.clone(),
+        curve: config.curve.clone(),
+        benchmark: config.benchmark,
+        exploration: config.exploration.clone(),
+        workers: config.workers,
+        database: config.database.clone(),
+    };
+    if let Err(e) = cp.save(&config.checkpoint) {
+        eprintln!("Cannot write the checkpoint: {}", e);
+    }
+}
+
+// Moves the evaluation of each position toward its depth-1 expectimax value.
+fn train_td0<T>(
+    config: &Config,
+    bench: &Benchmark,
+    mut explorer: Explorer,
+    mut evaluator: T,
+    games: u32
+)
+where T: Persistent + Clone
+{
+    let mut db = Database::default();
     // It converges in ~5000 games
-    for i in 1..=10000 {
+    for i in games + 1..=config.nb_games {
+        let mut rng = game_rng(config.seed, i);
         let mut board = Board::default();
-        loop {
+        let mut positions = Vec::new();
+        let mut exploratory = Vec::new();
+        let winner = loop {
+            positions.push(board.clone());
             let expectimax_val = expectimax(&evaluator, &board, 1);
             evaluator.step(&board, expectimax_val, ALPHA);
             let dice = roll_dices(&mut rng);
-            let moves = board.possible_moves(dice);
-            if moves.is_empty() {
+            if board.possible_moves(dice).is_empty() {
+                exploratory.push(false);
                 board.change_turn();
                 continue;
             }
-            let mut best_move = moves[0];
-            let mut best_val = eval_move(&evaluator, &board, dice, moves[0], 0);
-            for &m in moves.iter().skip(1) {
-                let val = eval_move(&evaluator, &board, dice, m, 0);
-                if val > best_val {
-                    best_move = m;
-                    best_val = val;
-                }
-            }
+            let before = explorer.exploratory;
+            let place = explorer.choose_move(&evaluator, &board, dice, i - 1, &mut rng);
+            exploratory.push(explorer.exploratory > before);
 
-            if board.perform_move(dice, best_move) {
-                break;
+            if board.perform_move(dice, place) {
+                break board.turn;
             }
+        };
+
+        let game = Game { positions, exploratory, winner };
+        progress(config, bench, &mut explorer, &mut db, &evaluator, &game, i);
+    }
+}
+
+// TD(λ) with eligibility traces over whole games.
+fn train_td_lambda<T>(
+    config: &Config,
+    bench: &Benchmark,
+    explorer: Explorer,
+    mut evaluator: T,
+    games: u32
+)
+where T: Persistent + Clone + Send + Sync
+{
+    let decay =
+        if config.model.starts_with("mlp") { MLP_DECAY } else { TD_LAMBDA_DECAY };
+    let mut trainer = TdLambda {
+        lambda: LAMBDA,
+        schedule: Schedule::InverseTime { initial: config.alpha, decay },
+        explorer,
+        games,
+    };
+    let mut db = Database::default();
+    if config.workers > 0 {
+        let parallel = ParallelSelfPlay {
+            nb_workers: config.workers,
+            sync: SYNC,
+            capacity: REPLAY_CAPACITY,
+            replay: REPLAY,
+        };
+        parallel.run(&mut trainer, &mut evaluator, config.seed, config.nb_games,
+                     |evaluator, trainer, game| {
+            progress(config, bench, &mut trainer.explorer, &mut db, evaluator, game,
+                     trainer.games);
+        });
+        return;
+    }
+    while trainer.games < config.nb_games {
+        let mut rng = game_rng(config.seed, trainer.games + 1);
+        let game = trainer.train_game(&mut evaluator, &mut rng);
+        progress(config, bench, &mut trainer.explorer, &mut db, &evaluator, &game,
+                 trainer.games);
+    }
+}
+
+// Fits the evaluator to the values of expectimax searches using the evaluator
+// of the teacher weight file, whatever its kind.
+fn train_supervised<T>(config: &Config, bench: &Benchmark, evaluator: T)
+where T: Persistent + Clone
+{
+    let model = match model_of(&config.teacher) {
+        Ok(model) => model,
+        Err(e) => {
+            eprintln!("Cannot read the teacher: {}", e);
+            return;
+        }
+    };
+    match model.split_whitespace().next() {
+        Some("linear") => fit::<T, LinearEval0>(config, bench, evaluator),
+        Some("tanh") => fit::<T, TanhEval0>(config, bench, evaluator),
+        Some("features") => fit::<T, FeatureEval>(config, bench, evaluator),
+        Some("mlp") => fit::<T, Mlp>(config, bench, evaluator),
+        _ => eprintln!("Unknown teacher model: {}", model),
+    }
+}
+
+// Fits the evaluator to the values of expectimax searches using the teacher
+// `H`, on positions sampled from its self-play. The weights are written after
+// each epoch.
+fn fit<T, H>(config: &Config, bench: &Benchmark, mut evaluator: T)
+where T: Persistent + Clone, H: Persistent
+{
+    // The positions come from the self-play of the teacher at depth 1.
+    let mut teacher = match ExpectimaxPlayer::<H>::load(&config.teacher, 1) {
+        Ok(player) => player,
+        Err(e) => {
+            eprintln!("Cannot read the teacher: {}", e);
+            return;
         }
+    };
+    let mut rng = game_rng(config.seed, 0);
+    let (positions, games) = sample_positions(&mut teacher, config.positions,
+                                              SUPERVISED_KEEP, &mut rng);
+    println!("Labeling {} positions from {} games at depth {}",
+             positions.len(), games, config.depth);
+    let mut samples = label(positions, |board| {
+        expectimax_value(&teacher.h, board, config.depth)
+    });
+    let split = samples.len() / VALIDATION;
+    let (validation, training) = samples.split_at_mut(split);
 
-        if i % 1000 == 0 {
-            println!("\nAfter {} games:", i);
-            evaluator.disp();
+    for epoch in 1..=config.epochs {
+        fit_epoch(&mut evaluator, training, BATCH_SIZE, config.alpha, &mut rng);
+        println!("Epoch {}: training error {}, validation error {}", epoch,
+                 mse(&evaluator, training), mse(&evaluator, validation));
+        // The evaluator is not trained on games: a self-play training started
+        // from these weights begins at 0 games.
+        if let Err(e) = save(&evaluator, &Metadata::new(0, config.alpha),
+                             &config.out) {
+            eprintln!("Cannot write the weights: {}", e);
+            return;
         }
     }
+
+    evaluator.disp();
+    if config.benchmark > 0 {
+        run_benchmark(bench, &evaluator);
+    }
 }
@@ -0,0 +1,153 @@
+use std::fs;
+use std::io;
+
+use crate::game_manager::*;
+use crate::reinforcement_learning::*;
+
+// Weight files store the parameters of a trained evaluator, together with
+// metadata about the training. They are text files:
+//
+// ur-weights <version>
+// model <description of the evaluator, see `Persistent::model`>
+// games <number of training games>
+// alpha <learning coefficient>
+// rules <rule variant>
+// params <number of parameters>
+// <one parameter per line>
+pub const VERSION: u32 = 1;
+
+pub struct Metadata {
+    // The number of games the evaluator was trained on.
+    pub games: u32,
+    // The (initial) learning coefficient.
+    pub alpha: f32,
+    // The rule variant the evaluator was trained with, see `RULES`.
+    pub rules: String,
+}
+
+impl Metadata {
+    // Metadata for the current rules.
+    pub fn new(games: u32, alpha: f32) -> Metadata {
+        Metadata { games, alpha, rules: RULES.to_string() }
+    }
+}
+
+// An evaluator that can be written to a weight file. Its parameters are the
+// ones of `TrainableHeuristic`.
+pub trait Persistent: TrainableHeuristic + Sized {
+    // A one-line description of the evaluator, from which an evaluator with the
+    // same structure can be built with `from_model`.
+    fn model(&self) -> String;
+    // Returns None if the description is invalid.
+    fn from_model(model: &str) -> Option<Self>;
+}
+
+impl Persistent for LinearEval0 {
+    fn model(&self) -> String {
+        "linear".to_string()
+    }
+
+    fn from_model(model: &str) -> Option<Self> {
+        if model == "linear" { Some(LinearEval0::default()) } else { None }
+    }
+}
+
+impl Persistent for TanhEval0 {
+    fn model(&self) -> String {
+        "tanh".to_string()
+    }
+
+    fn from_model(model: &str) -> Option<Self> {
+        if model == "tanh" { Some(TanhEval0::default()) } else { None }
+    }
+}
+
+impl Persistent for FeatureEval {
+    fn model(&self) -> String {
+        "features".to_string()
+    }
+
+    fn from_model(model: &str) -> Option<Self> {
+        if model == "features" { Some(FeatureEval::default()) } else { None }
+    }
+}
+
+// Writes `data` to `path` through a temporary file, so that an interruption
+// never leaves a partially written file.
+pub fn write_atomically(path: &str, data: impl AsRef<[u8]>) -> io::Result<()> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
+
+fn invalid(path: &str, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg))
+}
+
+// Writes the parameters of `h` and the metadata to `path`.
+pub fn save<T: Persistent>(h: &T, metadata: &Metadata, path: &str)
+    -> io::Result<()> {
+    let params = h.params();
+    let mut text = format!("ur-weights {}\n", VERSION);
+    text += &format!("model {}\n", h.model());
+    text += &format!("games {}\n", metadata.games);
+    text += &format!("alpha {}\n", metadata.alpha);
+    text += &format!("rules {}\n", metadata.rules);
+    text += &format!("params {}\n", params.len());
+    for p in params {
+        text += &format!("{}\n", p);
+    }
+    write_atomically(path, &text)
+}
+
+// Reads the description of the evaluator of a weight file, see
+// `Persistent::model`.
+pub fn model_of(path: &str) -> io::Result<String> {
+    let text = fs::read_to_string(path)?;
+    text.lines().nth(1)
+        .and_then(|l| l.strip_prefix("model "))
+        .map(|l| l.to_string())
+        .ok_or_else(|| invalid(path, "missing field model"))
+}
+
+// Reads an evaluator written by `save`. Fails if the file was written for
+// another kind of evaluator or for other rules.
+pub fn load<T: Persistent>(path: &str) -> io::Result<(T, Metadata)> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    let mut field = |name: &str| {
+        lines.next()
+            .and_then(|l| l.strip_prefix(name))
+            .and_then(|l| l.strip_prefix(' '))
+            .map(|l| l.to_string())
+            .ok_or_else(|| invalid(path, &format!("missing field {}", name)))
+    };
+
+    let version = field("ur-weights")?;
+    if version != VERSION.to_string() {
+        return Err(invalid(path, &format!("unsupported version {}", version)));
+    }
+    let model = field("model")?;
+    let mut h = T::from_model(&model)
+        .ok_or_else(|| invalid(path, &format!("unexpected model {}", model)))?;
+    let games = field("games")?.parse()
+        .map_err(|_| invalid(path, "invalid number of games"))?;
+    let alpha = field("alpha")?.parse()
+        .map_err(|_| invalid(path, "invalid learning coefficient"))?;
+    let rules = field("rules")?;
+    if rules != RULES {
+        return Err(invalid(path, &format!("trained for the rules {}", rules)));
+    }
+    let nb_params: usize = field("params")?.parse()
+        .map_err(|_| invalid(path, "invalid number of parameters"))?;
+
+    let params = lines.map(|l| l.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid(path, "invalid parameter"))?;
+    if params.len() != nb_params || nb_params != h.params().len() {
+        return Err(invalid(path, "wrong number of parameters"));
+    }
+    h.set_params(&params);
+
+    Ok((h, Metadata { games, alpha, rules }))
+}